@@ -0,0 +1,468 @@
+use crate::query_category::ValueVar;
+use crate::wikidata_queries::sparql_types::Object;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// `wikibase:timePrecision`, from 0 (billion years) to 14 (second).
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precision {
+    BillionYears,
+    HundredMillionYears,
+    TenMillionYears,
+    MillionYears,
+    HundredThousandYears,
+    TenThousandYears,
+    Millennium,
+    Century,
+    Decade,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl Precision {
+    pub fn from_wikibase(value: u8) -> Option<Self> {
+        use Precision::*;
+        [
+            BillionYears,
+            HundredMillionYears,
+            TenMillionYears,
+            MillionYears,
+            HundredThousandYears,
+            TenThousandYears,
+            Millennium,
+            Century,
+            Decade,
+            Year,
+            Month,
+            Day,
+            Hour,
+            Minute,
+            Second,
+        ]
+        .get(value as usize)
+        .copied()
+    }
+}
+
+/// `wikibase:timeCalendarModel`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Calendar {
+    Gregorian,
+    Julian,
+}
+
+impl Calendar {
+    pub fn from_iri(iri: &str) -> Option<Self> {
+        match iri {
+            "http://www.wikidata.org/entity/Q1985727" => Some(Calendar::Gregorian),
+            "http://www.wikidata.org/entity/Q1985786" => Some(Calendar::Julian),
+            _ => None,
+        }
+    }
+}
+
+/// A date as recorded by a source, e.g. `wikibase:timeValue` with its precision
+/// and calendar model.
+///
+/// `year`, `month` and `day` are proleptic Gregorian with astronomical year
+/// numbering, so year 0 is 1 BCE and -753 is 754 BCE; this is how the Wikidata
+/// RDF export and XSD 1.1 write them, converting Julian dates to Gregorian.
+/// `calendar` is the calendar the date was recorded in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HistoricalDate {
+    pub year: i64,
+    pub month: u8,
+    pub day: u8,
+    pub precision: Precision,
+    pub calendar: Calendar,
+}
+
+impl HistoricalDate {
+    /// Parses `[-]YYYY[-MM[-DD[Thh:mm:ssZ]]]`; unknown months and days may be `00`.
+    pub fn parse(value: &str, precision: Precision, calendar: Calendar) -> Option<Self> {
+        let (negative, rest) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        let date = rest.split('T').next()?;
+        let mut parts = date.split('-');
+        let year: i64 = parts.next()?.parse().ok()?;
+        let month: u8 = parts.next().map_or(Some(1), |m| m.parse().ok())?;
+        let day: u8 = parts.next().map_or(Some(1), |d| d.parse().ok())?;
+        if parts.next().is_some() || month > 12 || day > 31 {
+            return None;
+        }
+        Some(HistoricalDate {
+            year: if negative { -year } else { year },
+            month: month.max(1),
+            day: day.max(1),
+            precision,
+            calendar,
+        })
+    }
+
+    /// The date bound to `value`, with the precision and calendar model bound
+    /// to its time variables, or else those of its XSD datatype.
+    pub fn from_binding(binding: &HashMap<String, Object>, value: &ValueVar) -> Option<Self> {
+        let object = value.with_datatype(binding.get(&value.name)?);
+        let Some((precision, time)) = value
+            .time
+            .as_ref()
+            .and_then(|time| Some((binding.get(&time.precision)?, time)))
+        else {
+            return object.as_date();
+        };
+        let precision = Precision::from_wikibase(precision.value.parse().ok()?)?;
+        let calendar = binding
+            .get(&time.calendar)
+            .and_then(|calendar| Calendar::from_iri(&calendar.value))
+            .unwrap_or(Calendar::Gregorian);
+        Self::parse(&object.value, precision, calendar)
+    }
+
+    /// `(year, month, day)` of the same day in `calendar`.
+    pub fn in_calendar(&self, calendar: Calendar) -> (i64, u8, u8) {
+        from_julian_day(
+            julian_day(self.year, self.month, self.day, Calendar::Gregorian),
+            calendar,
+        )
+    }
+
+    /// The first and last Gregorian day the date may denote: a year-precision
+    /// date covers the whole year, `1500` at century precision the 15th century
+    /// (1401 to 1500), and `1500` at decade precision 1500 to 1509. The bounds
+    /// of a date recorded in the Julian calendar are those of the Julian year or
+    /// month, so the Julian year 1299 runs from 1299-01-08 to 1300-01-07.
+    pub fn interval(&self) -> ((i64, u8, u8), (i64, u8, u8)) {
+        let year_span = |first: i64, last: i64| ((first, 1, 1), (last, 12, 31));
+        let (first, last) = match self.precision {
+            Precision::Second | Precision::Minute | Precision::Hour | Precision::Day => {
+                let day = (self.year, self.month, self.day);
+                return (day, day);
+            }
+            Precision::Month => (
+                (self.year, self.month, 1),
+                (
+                    self.year,
+                    self.month,
+                    days_in_month(self.year, self.month, self.calendar),
+                ),
+            ),
+            Precision::Year => year_span(self.year, self.year),
+            // Centuries and millennia are ordinal: the 15th century ends with 1500,
+            // and the 5th century BCE runs from 500 BCE (-499) to 401 BCE (-400).
+            Precision::Century | Precision::Millennium => {
+                let size = if self.precision == Precision::Century {
+                    100
+                } else {
+                    1000
+                };
+                if self.year > 0 {
+                    let ordinal = (self.year + size - 1) / size;
+                    year_span((ordinal - 1) * size + 1, ordinal * size)
+                } else {
+                    let ordinal = (1 - self.year + size - 1) / size;
+                    year_span(1 - ordinal * size, -(ordinal - 1) * size)
+                }
+            }
+            precision => {
+                let size = 10_i64.pow(Precision::Year as u32 - precision as u32);
+                let first = self.year.div_euclid(size) * size;
+                year_span(first, first + size - 1)
+            }
+        };
+        let gregorian = |(year, month, day)| {
+            from_julian_day(
+                julian_day(year, month, day, self.calendar),
+                Calendar::Gregorian,
+            )
+        };
+        (gregorian(first), gregorian(last))
+    }
+
+    /// The date as recorded, cut down to its precision, e.g. `-0753` or `1299-07-27`.
+    pub fn value(&self) -> String {
+        let (year, month, day) = if self.precision >= Precision::Day {
+            self.in_calendar(self.calendar)
+        } else {
+            (self.year, self.month, self.day)
+        };
+        match self.precision {
+            p if p >= Precision::Day => format_day((year, month, day)),
+            Precision::Month => format!("{}-{:02}", format_year(year), month),
+            _ => format_year(year),
+        }
+    }
+}
+
+fn format_year(year: i64) -> String {
+    if year < 0 {
+        format!("-{:04}", -year)
+    } else {
+        format!("{:04}", year)
+    }
+}
+
+fn format_day((year, month, day): (i64, u8, u8)) -> String {
+    format!("{}-{:02}-{:02}", format_year(year), month, day)
+}
+
+fn is_leap_year(year: i64, calendar: Calendar) -> bool {
+    match calendar {
+        Calendar::Julian => year.rem_euclid(4) == 0,
+        Calendar::Gregorian => {
+            year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0)
+        }
+    }
+}
+
+fn days_in_month(year: i64, month: u8, calendar: Calendar) -> u8 {
+    match month {
+        2 if is_leap_year(year, calendar) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Julian day number of a date with astronomical year numbering.
+fn julian_day(year: i64, month: u8, day: u8, calendar: Calendar) -> i64 {
+    let a = (14 - month as i64) / 12;
+    let y = year + 4800 - a;
+    let m = month as i64 + 12 * a - 3;
+    let days = day as i64 + (153 * m + 2) / 5 + 365 * y + y.div_euclid(4);
+    match calendar {
+        Calendar::Gregorian => days - y.div_euclid(100) + y.div_euclid(400) - 32045,
+        Calendar::Julian => days - 32083,
+    }
+}
+
+fn from_julian_day(jdn: i64, calendar: Calendar) -> (i64, u8, u8) {
+    let (b, c) = match calendar {
+        Calendar::Gregorian => {
+            let a = jdn + 32044;
+            let b = (4 * a + 3).div_euclid(146097);
+            (b, a - (146097 * b).div_euclid(4))
+        }
+        Calendar::Julian => (0, jdn + 32082),
+    };
+    let d = (4 * c + 3).div_euclid(1461);
+    let e = c - (1461 * d).div_euclid(4);
+    let m = (5 * e + 2) / 153;
+    let day = e - (153 * m + 2) / 5 + 1;
+    let month = m + 3 - 12 * (m / 10);
+    let year = 100 * b + d - 4800 + m / 10;
+    (year, month as u8, day as u8)
+}
+
+/// How a date is written to the merged output.
+#[derive(Serialize, Deserialize)]
+struct DateRecord {
+    value: String,
+    precision: Precision,
+    calendar: Calendar,
+    earliest: String,
+    latest: String,
+}
+
+impl Serialize for HistoricalDate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (earliest, latest) = self.interval();
+        DateRecord {
+            value: self.value(),
+            precision: self.precision,
+            calendar: self.calendar,
+            earliest: format_day(earliest),
+            latest: format_day(latest),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HistoricalDate {
+    /// Reads a date written by `serialize`; days are read from `earliest`,
+    /// which is Gregorian whatever calendar `value` is in.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let record = DateRecord::deserialize(deserializer)?;
+        let value = if record.precision >= Precision::Day {
+            &record.earliest
+        } else {
+            &record.value
+        };
+        Self::parse(value, record.precision, record.calendar)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid date: {}", record.value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wikidata_queries::time_var;
+
+    fn binding(pairs: &[(&str, &str)]) -> HashMap<String, Object> {
+        pairs
+            .iter()
+            .map(|(key, value)| {
+                let object = Object {
+                    value: value.to_string(),
+                    ..Object::default()
+                };
+                (key.to_string(), object)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_from_binding() {
+        let year = binding(&[
+            ("inception", "1500-01-01T00:00:00Z"),
+            ("inceptionPrecision", "9"),
+            (
+                "inceptionCalendar",
+                "http://www.wikidata.org/entity/Q1985786",
+            ),
+        ]);
+        let date = HistoricalDate::from_binding(&year, &time_var("inception")).unwrap();
+        assert_eq!(
+            serde_json::to_value(date).unwrap(),
+            serde_json::json!({
+                "value": "1500",
+                "precision": "year",
+                "calendar": "julian",
+                "earliest": "1500-01-10",
+                "latest": "1501-01-10",
+            })
+        );
+
+        // Without precision the XSD datatype decides, and a plain literal is no date.
+        let founding_date = ValueVar::plain("foundingDate");
+        let mut typed = binding(&[("foundingDate", "1776-07-04")]);
+        assert_eq!(HistoricalDate::from_binding(&typed, &founding_date), None);
+        typed.get_mut("foundingDate").unwrap().datatype =
+            Some("http://www.w3.org/2001/XMLSchema#date".to_string());
+        let date = HistoricalDate::from_binding(&typed, &founding_date).unwrap();
+        // A time value whose precision is not bound falls back to its datatype.
+        assert_eq!(
+            HistoricalDate::from_binding(&typed, &time_var("foundingDate")),
+            Some(date)
+        );
+        assert_eq!(date.precision, Precision::Day);
+        assert_eq!(date.value(), "1776-07-04");
+    }
+
+    #[test]
+    fn test_bce_and_year_zero() {
+        let date = HistoricalDate::parse(
+            "-0753-04-21T00:00:00Z",
+            Precision::Year,
+            Calendar::Gregorian,
+        )
+        .unwrap();
+        assert_eq!(date.year, -753);
+        assert_eq!(date.value(), "-0753");
+        assert_eq!(date.interval(), ((-753, 1, 1), (-753, 12, 31)));
+
+        // Year 0 is 1 BCE, a leap year in both calendars.
+        let date =
+            HistoricalDate::parse("0000-02-29", Precision::Day, Calendar::Gregorian).unwrap();
+        assert_eq!(date.in_calendar(Calendar::Julian), (0, 3, 2));
+        assert!(is_leap_year(0, Calendar::Gregorian));
+
+        assert_eq!(
+            HistoricalDate::parse("1500-13-01", Precision::Day, Calendar::Gregorian),
+            None
+        );
+        assert_eq!(
+            HistoricalDate::parse("unknown", Precision::Day, Calendar::Gregorian),
+            None
+        );
+    }
+
+    #[test]
+    fn test_julian_to_gregorian() {
+        assert_eq!(julian_day(2000, 1, 1, Calendar::Gregorian), 2451545);
+        // The day after Julian 4 October 1582 was Gregorian 15 October 1582.
+        let jdn = julian_day(1582, 10, 4, Calendar::Julian);
+        assert_eq!(
+            from_julian_day(jdn + 1, Calendar::Gregorian),
+            (1582, 10, 15)
+        );
+        for jdn in [0, 1721424, 2299160, 2451545] {
+            for calendar in [Calendar::Julian, Calendar::Gregorian] {
+                let (year, month, day) = from_julian_day(jdn, calendar);
+                assert_eq!(julian_day(year, month, day, calendar), jdn);
+            }
+        }
+
+        // Recorded in Julian, stored by Wikidata converted to Gregorian.
+        let date = HistoricalDate::parse("1299-08-06", Precision::Day, Calendar::Julian).unwrap();
+        assert_eq!(date.value(), "1299-07-30");
+        let json = serde_json::to_string(&date).unwrap();
+        assert_eq!(serde_json::from_str::<HistoricalDate>(&json).unwrap(), date);
+    }
+
+    #[test]
+    fn test_precision_intervals() {
+        let at = |value, precision| {
+            HistoricalDate::parse(value, precision, Calendar::Gregorian)
+                .unwrap()
+                .interval()
+        };
+        assert_eq!(
+            at("1500-00-00", Precision::Century),
+            ((1401, 1, 1), (1500, 12, 31))
+        );
+        assert_eq!(
+            at("1450-01-01", Precision::Century),
+            ((1401, 1, 1), (1500, 12, 31))
+        );
+        assert_eq!(
+            at("-0450-01-01", Precision::Century),
+            ((-499, 1, 1), (-400, 12, 31))
+        );
+        assert_eq!(
+            at("1505-01-01", Precision::Decade),
+            ((1500, 1, 1), (1509, 12, 31))
+        );
+        assert_eq!(
+            at("2001-01-01", Precision::Millennium),
+            ((2001, 1, 1), (3000, 12, 31))
+        );
+        assert_eq!(
+            at("-12000-01-01", Precision::TenThousandYears),
+            ((-20000, 1, 1), (-10001, 12, 31))
+        );
+        assert_eq!(
+            at("1900-02-10", Precision::Month),
+            ((1900, 2, 1), (1900, 2, 28))
+        );
+    }
+
+    #[test]
+    fn test_julian_intervals() {
+        let at = |value, precision| {
+            HistoricalDate::parse(value, precision, Calendar::Julian)
+                .unwrap()
+                .interval()
+        };
+        assert_eq!(
+            at("1299-00-00", Precision::Year),
+            ((1299, 1, 8), (1300, 1, 7))
+        );
+        assert_eq!(
+            at("1500-00-00", Precision::Century),
+            ((1401, 1, 10), (1501, 1, 10))
+        );
+        // February 1900 has 29 days in the Julian calendar.
+        assert_eq!(
+            at("1900-02-00", Precision::Month),
+            ((1900, 2, 13), (1900, 3, 13))
+        );
+    }
+}
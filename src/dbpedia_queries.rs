@@ -1,3 +1,15 @@
 pub mod battle;
+pub mod capital;
 pub mod country;
 pub mod get_query;
+
+use crate::query_builder::{literal, str, strstarts, triple, var, Pattern, Prefix};
+
+/// `?{subject} owl:sameAs ?sameAs` restricted to Wikidata entities, which is how
+/// merge folds DBpedia resources into their Wikidata entity.
+pub fn same_as_wikidata(subject: &str) -> Vec<Pattern> {
+    vec![
+        triple(var(subject), Prefix::Owl.term("sameAs"), var("sameAs")),
+        Pattern::Filter(strstarts(str(var("sameAs")), literal(Prefix::Wd.iri()))),
+    ]
+}
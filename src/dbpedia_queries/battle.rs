@@ -1,54 +1,91 @@
+use crate::dbpedia_queries::same_as_wikidata;
+use crate::merge::{merger, Merger, SAME_AS};
+use crate::model;
+use crate::query_builder::{bind, dbo, lang, triple, var, Pattern, Prefix, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement, ValueVar};
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
 pub enum BattleQuery {
     #[strum(serialize = "abstract")]
     Abstract,
+    #[strum(serialize = "sameAs")]
+    SameAs,
     #[default]
     Unknown,
 }
 
-static BATTLE_QUERY: &str = "
-    ?battle rdf:type dbo:MilitaryConflict .
-    ?battle rdf:type dbo:Event .
-";
-
-pub fn gen_battle_query(battle_query: BattleQuery) -> String {
-    let result = match battle_query {
-        BattleQuery::Abstract => {
-            format!(
-                "
-                prefix rdfs: <http://www.w3.org/2000/01/rdf-schema#>
-                prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
-                prefix dbo: <http://dbpedia.org/ontology/>
-                select distinct ?battle ?abstract ?language where {{
-                    {}
-                    ?battle dbo:abstract ?abstract .
-                    BIND (LANG(?abstract) AS ?language)
-                }}",
-                BATTLE_QUERY
-            )
-        }
+fn battle_patterns() -> Vec<Pattern> {
+    let rdf_type = || Prefix::Rdf.term("type");
+    vec![
+        triple(var("battle"), rdf_type(), dbo("MilitaryConflict")),
+        triple(var("battle"), rdf_type(), dbo("Event")),
+    ]
+}
+
+pub fn gen_battle_query(battle_query: BattleQuery) -> Option<Query> {
+    let select = |vars: &[&str]| Query::select_distinct(vars).patterns(battle_patterns());
+    let query = match battle_query {
+        BattleQuery::Abstract => select(&["battle", "abstract", "language"])
+            .pattern(triple(var("battle"), dbo("abstract"), var("abstract")))
+            .pattern(bind(lang(var("abstract")), "language")),
+        BattleQuery::SameAs => select(&["battle", "sameAs"]).patterns(same_as_wikidata("battle")),
+        BattleQuery::Unknown => return None,
+    };
+    Some(query)
+}
 
-        BattleQuery::Unknown => "Unknown".to_string(),
+/// How merge reads the bindings of each query of `gen_battle_query`.
+pub fn battle_statement(battle_query: BattleQuery) -> Option<Statement> {
+    let statement = match battle_query {
+        BattleQuery::Abstract => Statement::text("abstract", "language"),
+        BattleQuery::SameAs => Statement::new(ValueVar::plain(SAME_AS)),
+        BattleQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct Battle;
+
+impl QueryCategory for Battle {
+    fn name(&self) -> &str {
+        "battle"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<BattleQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<BattleQuery>(target).and_then(gen_battle_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<BattleQuery>(target).and_then(battle_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Battle>()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::endpoint::Dialect;
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_abstract() {
+    fn test_queries_use_dbpedia_prefixes() {
         for variant in BattleQuery::iter() {
             if variant == BattleQuery::Unknown {
                 continue;
             }
-            let query = gen_battle_query(variant);
+            let query = gen_battle_query(variant).unwrap();
+            assert_eq!(query.vars[0], "battle");
+            assert!(query.patterns.starts_with(&battle_patterns()));
             assert!(
-                query.starts_with("prefix"),
+                query.render(Dialect::Dbpedia).is_ok(),
                 "Failed for variant: {:?}",
                 variant
             );
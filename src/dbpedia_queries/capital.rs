@@ -0,0 +1,105 @@
+use crate::dbpedia_queries::same_as_wikidata;
+use crate::merge::{merger, Merger, SAME_AS};
+use crate::model;
+use crate::query_builder::{dbo, labelled, triple, var, Prefix, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement, ValueVar};
+use oxigraph::model::vocab::geosparql;
+use strum_macros::{Display, EnumIter, EnumString};
+
+#[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
+pub enum CapitalQuery {
+    #[strum(serialize = "label")]
+    Label,
+    #[strum(serialize = "coordinates")]
+    Coordinates,
+    #[strum(serialize = "sameAs")]
+    SameAs,
+    #[default]
+    Unknown,
+}
+
+pub fn gen_capital_query(capital_query: CapitalQuery) -> Option<Query> {
+    let select = |vars: &[&str]| {
+        Query::select_distinct(vars)
+            .pattern(triple(
+                var("country"),
+                Prefix::Rdf.term("type"),
+                dbo("Country"),
+            ))
+            .pattern(triple(var("country"), dbo("capital"), var("capital")))
+    };
+    let query = match capital_query {
+        CapitalQuery::Label => {
+            select(&["capital", "label", "language"]).patterns(labelled("capital"))
+        }
+        CapitalQuery::Coordinates => select(&["capital", "coordinates"]).pattern(triple(
+            var("capital"),
+            Prefix::Geo.term("geometry"),
+            var("coordinates"),
+        )),
+        // Lets merge fold the `dbo:capital` of a country into the Wikidata
+        // capital it stands for.
+        CapitalQuery::SameAs => {
+            select(&["capital", "sameAs"]).patterns(same_as_wikidata("capital"))
+        }
+        CapitalQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_capital_query`.
+pub fn capital_statement(capital_query: CapitalQuery) -> Option<Statement> {
+    let statement = match capital_query {
+        CapitalQuery::Label => Statement::text("label", "language"),
+        CapitalQuery::Coordinates => {
+            Statement::new(ValueVar::plain("coordinates")).typed(geosparql::WKT_LITERAL.as_str())
+        }
+        CapitalQuery::SameAs => Statement::new(ValueVar::plain(SAME_AS)),
+        CapitalQuery::Unknown => return None,
+    };
+    Some(statement)
+}
+
+pub struct Capital;
+
+impl QueryCategory for Capital {
+    fn name(&self) -> &str {
+        "capital"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<CapitalQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<CapitalQuery>(target).and_then(gen_capital_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<CapitalQuery>(target).and_then(capital_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Capital>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Dialect;
+    use strum::IntoEnumIterator;
+
+    #[test]
+    fn test_queries_select_distinct_capitals() {
+        for variant in CapitalQuery::iter() {
+            if variant == CapitalQuery::Unknown {
+                continue;
+            }
+            let query = gen_capital_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "capital");
+            assert!(query.render(Dialect::Dbpedia).is_ok());
+        }
+    }
+}
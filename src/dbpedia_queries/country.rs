@@ -1,3 +1,9 @@
+use crate::dbpedia_queries::same_as_wikidata;
+use crate::merge::{merger, Merger, SAME_AS};
+use crate::model;
+use crate::query_builder::{dbo, labelled, triple, var, Prefix, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement, ValueVar};
+use oxigraph::model::vocab::{geosparql, xsd};
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
@@ -14,107 +20,106 @@ pub enum CountryQuery {
     Label,
     #[strum(serialize = "flag")]
     Flag,
+    #[strum(serialize = "sameAs")]
+    SameAs,
     #[default]
     Unknown,
 }
 
-pub static COUNTRY_QUERY: &str = "
-{
-?country wdt:P31 wd:Q6256 .
-} UNION {
-?country wdt:P31 wd:Q3024240 .
+pub fn gen_country_query(country_query: CountryQuery) -> Option<Query> {
+    let select = |vars: &[&str]| {
+        Query::select_distinct(vars).pattern(triple(
+            var("country"),
+            Prefix::Rdf.term("type"),
+            dbo("Country"),
+        ))
+    };
+    let property = |target: &str, property| {
+        select(&["country", target]).pattern(triple(var("country"), property, var(target)))
+    };
+    let query = match country_query {
+        CountryQuery::Inception => property("inception", dbo("foundingDate")),
+        CountryQuery::Dissolution => property("dissolution", dbo("dissolutionDate")),
+        // A WKT `POINT(lon lat)` literal, like wdt:P625 on Wikidata.
+        CountryQuery::Coordinates => property("coordinates", Prefix::Geo.term("geometry")),
+        CountryQuery::Capital => property("capital", dbo("capital")),
+        CountryQuery::Label => {
+            select(&["country", "label", "language"]).patterns(labelled("country"))
+        }
+        CountryQuery::Flag => property("flag", Prefix::Dbp.term("imageFlag")),
+        CountryQuery::SameAs => {
+            select(&["country", "sameAs"]).patterns(same_as_wikidata("country"))
+        }
+        CountryQuery::Unknown => return None,
+    };
+    Some(query)
 }
-";
 
-pub fn gen_country_query(country_query: CountryQuery) -> String {
-    let result = match country_query {
+/// How merge reads the bindings of each query of `gen_country_query`.
+pub fn country_statement(country_query: CountryQuery) -> Option<Statement> {
+    let statement = match country_query {
         CountryQuery::Inception => {
-            format!(
-                "SELECT DISTINCT ?country ?inception WHERE {{
-                    {}
-                    ?country wdt:P571 ?inception .
-                }}",
-                COUNTRY_QUERY
-            )
+            Statement::new(ValueVar::plain("inception")).typed(xsd::DATE.as_str())
         }
         CountryQuery::Dissolution => {
-            format!(
-                "SELECT DISTINCT ?country ?dissolution WHERE {{
-                    {}
-                    ?country wdt:P576 ?dissolution .
-                }}",
-                COUNTRY_QUERY
-            )
+            Statement::new(ValueVar::plain("dissolution")).typed(xsd::DATE.as_str())
         }
         CountryQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?country ?coordinates WHERE {{
-                    {}
-                    ?country wdt:P625 ?coordinates .
-                }}",
-                COUNTRY_QUERY
-            )
+            Statement::new(ValueVar::plain("coordinates")).typed(geosparql::WKT_LITERAL.as_str())
         }
-        CountryQuery::Capital => {
-            format!(
-                "SELECT DISTINCT ?country ?capital ?startTime ?endTime ?pointInTime WHERE {{
-                    {}
-                    ?country p:P36 ?capital_statement .
-                    ?capital_statement ps:P36 ?capital .
-                    OPTIONAL {{
-                        ?capital_statement pq:P580 ?startTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P582 ?endTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P585 ?pointInTime .
-                    }}
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?country ?label ?language WHERE {{
-                    {}
-                    ?country rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Flag => {
-            format!(
-                "SELECT DISTINCT ?country ?flag WHERE {{
-                    {}
-                    ?country wdt:P41 ?flag .
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Unknown => "Unknown".to_string(),
+        CountryQuery::Capital => Statement::new(ValueVar::plain("capital")),
+        CountryQuery::Label => Statement::text("label", "language"),
+        CountryQuery::Flag => Statement::new(ValueVar::plain("flag")),
+        CountryQuery::SameAs => Statement::new(ValueVar::plain(SAME_AS)),
+        CountryQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct Country;
+
+impl QueryCategory for Country {
+    fn name(&self) -> &str {
+        "country"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<CountryQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<CountryQuery>(target).and_then(gen_country_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<CountryQuery>(target).and_then(country_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Country>()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::endpoint::Dialect;
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_use_dbpedia_prefixes() {
         for variant in CountryQuery::iter() {
             if variant == CountryQuery::Unknown {
                 continue;
             }
-            let query = gen_country_query(variant);
+            let query = gen_country_query(variant).unwrap();
+            assert_eq!(query.vars[0], "country");
             assert!(
-                query.starts_with("SELECT DISTINCT"),
+                query.render(Dialect::Dbpedia).is_ok(),
                 "Failed for variant: {:?}",
                 variant
             );
+            assert!(query.render(Dialect::Wikidata).is_err());
         }
     }
 }
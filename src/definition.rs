@@ -0,0 +1,440 @@
+use crate::endpoint::Dialect;
+use crate::error::Error;
+use crate::query_builder::{bind, lang, pq, triple, var, wdt, Path, Pattern, Prefix, Query, Term};
+use crate::query_category::{QueryCategory, Statement, ValueVar};
+use crate::wikidata_queries::{rank_var, statement_value};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path as FilePath;
+use std::str::FromStr;
+
+/// A category described in a TOML or YAML file instead of Rust, e.g.
+///
+/// ```toml
+/// name = "country"
+/// dialect = "wikidata"
+/// classes = ["wd:Q6256", "wd:Q3024240"]
+///
+/// [[targets]]
+/// name = "population"
+/// property = "P1082"
+/// qualifiers = { pointInTime = "P585" }
+/// datatype = "<http://www.w3.org/2001/XMLSchema#decimal>"
+///
+/// [[targets]]
+/// name = "demonym"
+/// path = "wdt:P1549"
+/// language = true
+/// ```
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Definition {
+    pub name: String,
+    pub dialect: Dialect,
+    /// Variable that binds the entity; the category name by default.
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// The entity is an instance of one of these classes...
+    pub classes: Vec<String>,
+    /// ...through this path, `wdt:P31` on Wikidata and `rdf:type` on DBpedia by default...
+    #[serde(default)]
+    pub class_path: Option<String>,
+    /// ...and of none of these.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    pub targets: Vec<TargetDefinition>,
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetDefinition {
+    /// Target name and the variable its values are bound to.
+    pub name: String,
+    /// Property path from the entity to the value, e.g. `wdt:P1549`.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Wikidata property id, e.g. `P1082`; required for qualifiers.
+    #[serde(default)]
+    pub property: Option<String>,
+    /// Optional qualifiers of the statement, as variable -> property id.
+    #[serde(default)]
+    pub qualifiers: BTreeMap<String, String>,
+    /// Bind the language tag of the value to `?language`.
+    #[serde(default)]
+    pub language: bool,
+    /// Datatype of its literal values, e.g. `<http://www.w3.org/2001/XMLSchema#decimal>`,
+    /// so that they are also typed in results that drop datatypes, like CSV.
+    #[serde(default)]
+    pub datatype: Option<String>,
+    /// Projected variables; the subject, the value, `language`, the rank of
+    /// the statements of a property and the qualifiers by default. Merge reads
+    /// all but the subject, so they may be reordered but not left out.
+    #[serde(default)]
+    pub vars: Option<Vec<String>>,
+}
+
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_property_id(id: &str) -> bool {
+    id.strip_prefix('P')
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    match Term::from_str(text)? {
+        Term::Var(_) => Err(format!("expected an IRI, got {}", text)),
+        term => Ok(term),
+    }
+}
+
+impl Definition {
+    pub fn compile(&self) -> Result<DeclarativeCategory, String> {
+        let subject = self.subject.clone().unwrap_or_else(|| self.name.clone());
+        if !is_var_name(&subject) {
+            return Err(format!("invalid subject variable: {}", subject));
+        }
+        if self.classes.is_empty() {
+            return Err("no classes".to_string());
+        }
+        let class_path = match &self.class_path {
+            Some(path) => Path::from_str(path)?,
+            None => match self.dialect {
+                Dialect::Wikidata => Path::from(wdt("P31")),
+                Dialect::Dbpedia => Path::from(Prefix::Rdf.term("type")),
+            },
+        };
+        let instance_of = |class: &String| {
+            Ok(triple(
+                var(&subject),
+                class_path.clone(),
+                parse_term(class)?,
+            ))
+        };
+        let mut root = vec![];
+        let mut classes = self
+            .classes
+            .iter()
+            .map(instance_of)
+            .collect::<Result<Vec<_>, String>>()?;
+        if classes.len() == 1 {
+            root.append(&mut classes);
+        } else {
+            root.push(Pattern::Union(
+                classes.into_iter().map(|c| vec![c]).collect(),
+            ));
+        }
+        for class in &self.exclude {
+            root.push(Pattern::FilterNotExists(vec![instance_of(class)?]));
+        }
+
+        let mut targets: Vec<(String, Query, Statement)> = vec![];
+        for target in &self.targets {
+            if targets.iter().any(|(name, _, _)| *name == target.name) {
+                return Err(format!("duplicate target: {}", target.name));
+            }
+            let query = target
+                .compile(&subject, &root)
+                .map_err(|e| format!("target {}: {}", target.name, e))?;
+            query
+                .render(self.dialect)
+                .map_err(|e| format!("target {}: {}", target.name, e))?;
+            let statement = target
+                .statement()
+                .map_err(|e| format!("target {}: {}", target.name, e))?;
+            let vars = statement.vars();
+            if let Some(name) = vars.iter().find(|name| !query.vars.contains(name)) {
+                return Err(format!(
+                    "target {}: ?{} is read by merge but not projected",
+                    target.name, name
+                ));
+            }
+            targets.push((target.name.clone(), query, statement));
+        }
+        Ok(DeclarativeCategory {
+            name: self.name.clone(),
+            subject,
+            targets,
+        })
+    }
+}
+
+impl TargetDefinition {
+    fn compile(&self, subject: &str, root: &[Pattern]) -> Result<Query, String> {
+        let value = &self.name;
+        let mut bound = vec![subject.to_string(), value.clone()];
+        let mut patterns = root.to_vec();
+        match (&self.path, &self.property) {
+            (Some(path), None) => {
+                if !self.qualifiers.is_empty() {
+                    return Err("qualifiers need a property instead of a path".to_string());
+                }
+                patterns.push(triple(var(subject), Path::from_str(path)?, var(value)));
+            }
+            (None, Some(property)) if is_property_id(property) => {
+                patterns.extend(statement_value(subject, property, value));
+                bound.push(rank_var(value));
+                let statement = format!("{}_statement", value);
+                for (qualifier, property) in &self.qualifiers {
+                    if !is_property_id(property) {
+                        return Err(format!("invalid property id: {}", property));
+                    }
+                    patterns.push(Pattern::Optional(vec![triple(
+                        var(&statement),
+                        pq(property),
+                        var(qualifier),
+                    )]));
+                    bound.push(qualifier.clone());
+                }
+            }
+            (None, Some(property)) => return Err(format!("invalid property id: {}", property)),
+            _ => return Err("exactly one of path and property is required".to_string()),
+        }
+        if self.language {
+            patterns.push(bind(lang(var(value)), "language"));
+            bound.insert(2, "language".to_string());
+        }
+        if let Some(name) = bound.iter().find(|name| !is_var_name(name)) {
+            return Err(format!("invalid variable name: {}", name));
+        }
+
+        let vars = self.vars.clone().unwrap_or(bound.clone());
+        if let Some(name) = vars.iter().find(|name| !bound.contains(name)) {
+            return Err(format!("?{} is not bound by the query", name));
+        }
+        let vars: Vec<&str> = vars.iter().map(String::as_str).collect();
+        Ok(Query::select_distinct(&vars).patterns(patterns))
+    }
+
+    /// How merge reads the bindings of the query, whatever order `vars` projects them in.
+    fn statement(&self) -> Result<Statement, String> {
+        let mut statement = if self.language {
+            Statement::text(&self.name, "language")
+        } else {
+            Statement::new(ValueVar::plain(&self.name))
+        };
+        if self.property.is_some() {
+            statement = statement.rank(rank_var(&self.name));
+        }
+        for qualifier in self.qualifiers.keys() {
+            statement = statement.qualifier(ValueVar::plain(qualifier));
+        }
+        match self.datatype.as_deref().map(parse_term).transpose()? {
+            Some(Term::Iri(iri)) => statement = statement.typed(&iri),
+            Some(Term::Prefixed(prefix, local)) => {
+                statement = statement.typed(&format!("{}{}", prefix.iri(), local))
+            }
+            Some(_) => {
+                return Err(format!(
+                    "invalid datatype: {}",
+                    self.datatype.as_deref().unwrap_or_default()
+                ))
+            }
+            None => {}
+        }
+        Ok(statement)
+    }
+}
+
+/// A category compiled from a [`Definition`]; every query is built once at load time.
+pub struct DeclarativeCategory {
+    name: String,
+    subject: String,
+    targets: Vec<(String, Query, Statement)>,
+}
+
+impl QueryCategory for DeclarativeCategory {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    fn targets(&self) -> Vec<String> {
+        self.targets
+            .iter()
+            .map(|(name, _, _)| name.clone())
+            .collect()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        self.targets
+            .iter()
+            .find(|(name, _, _)| name == target)
+            .map(|(_, query, _)| query.clone())
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        self.targets
+            .iter()
+            .find(|(name, _, _)| name == target)
+            .map(|(_, _, statement)| statement.clone())
+    }
+}
+
+/// Every `*.toml`, `*.yaml` and `*.yml` definition in `directory`, by file name.
+pub fn load(directory: &FilePath) -> Result<Vec<(Dialect, DeclarativeCategory)>, Error> {
+    let entries = fs::read_dir(directory).map_err(|e| Error::io(directory, e))?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io(directory, e))?.path();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        if path.is_file() && ["toml", "yaml", "yml"].contains(&extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut categories = vec![];
+    for path in paths {
+        let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+        let parsed: Result<Definition, String> = if path.extension().is_some_and(|e| e == "toml") {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            serde_yaml::from_str(&text).map_err(|e| e.to_string())
+        };
+        let category = parsed
+            .and_then(|definition| Ok((definition.dialect, definition.compile()?)))
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        categories.push(category);
+    }
+    Ok(categories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POPULATION: &str = r#"
+name = "country"
+dialect = "wikidata"
+classes = ["wd:Q6256", "wd:Q3024240"]
+
+[[targets]]
+name = "population"
+property = "P1082"
+qualifiers = { pointInTime = "P585" }
+datatype = "<http://www.w3.org/2001/XMLSchema#decimal>"
+
+[[targets]]
+name = "demonym"
+path = "wdt:P1549"
+language = true
+"#;
+
+    #[test]
+    fn test_compile_toml() {
+        let definition: Definition = toml::from_str(POPULATION).unwrap();
+        let category = definition.compile().unwrap();
+        assert_eq!(category.subject(), "country");
+        assert_eq!(category.targets(), vec!["population", "demonym"]);
+
+        let query = category.gen_query("population").unwrap();
+        assert_eq!(
+            query.vars,
+            vec!["country", "population", "populationRank", "pointInTime"]
+        );
+        assert!(query.patterns.contains(&Pattern::Optional(vec![triple(
+            var("population_statement"),
+            pq("P585"),
+            var("pointInTime"),
+        )])));
+
+        let query = category.gen_query("demonym").unwrap();
+        assert_eq!(query.vars, vec!["country", "demonym", "language"]);
+        let sparql = query.render(Dialect::Wikidata).unwrap();
+        assert!(sparql.contains("?country wdt:P1549 ?demonym ."));
+        assert!(sparql.contains("} UNION {"));
+    }
+
+    #[test]
+    fn test_statement_of_custom_vars() {
+        let mut definition: Definition = toml::from_str(POPULATION).unwrap();
+        definition.targets[0].vars = Some(
+            ["country", "pointInTime", "populationRank", "population"]
+                .map(String::from)
+                .to_vec(),
+        );
+        let category = definition.compile().unwrap();
+        assert_eq!(
+            category.statement("population"),
+            Some(
+                Statement::new(ValueVar::plain("population"))
+                    .rank("populationRank".to_string())
+                    .qualifier(ValueVar::plain("pointInTime"))
+                    .typed("http://www.w3.org/2001/XMLSchema#decimal")
+            )
+        );
+        assert_eq!(
+            category.statement("demonym"),
+            Some(Statement::text("demonym", "language"))
+        );
+        assert_eq!(category.statement("hoge"), None);
+    }
+
+    #[test]
+    fn test_vars_project_the_statement() {
+        let definition: Definition = toml::from_str(
+            r#"
+name = "country"
+dialect = "wikidata"
+classes = ["wd:Q6256"]
+
+[[targets]]
+name = "population"
+property = "P1082"
+vars = ["country", "population"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            definition.compile().err(),
+            Some(
+                "target population: ?populationRank is read by merge but not projected".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_compile_yaml() {
+        let definition: Definition = serde_yaml::from_str(
+            "
+name: treaty
+dialect: dbpedia
+classes: [dbo:Treaty]
+targets:
+  - name: abstract
+    path: dbo:abstract
+    language: true
+",
+        )
+        .unwrap();
+        let query = definition.compile().unwrap().gen_query("abstract").unwrap();
+        let sparql = query.render(Dialect::Dbpedia).unwrap();
+        assert!(sparql.contains("?treaty rdf:type dbo:Treaty ."));
+    }
+
+    #[test]
+    fn test_invalid_definitions() {
+        let compile = |patch: &dyn Fn(&mut Definition)| {
+            let mut definition: Definition = toml::from_str(POPULATION).unwrap();
+            patch(&mut definition);
+            definition.compile().err()
+        };
+        assert_eq!(compile(&|_| {}), None);
+        assert!(compile(&|d| d.dialect = Dialect::Dbpedia).is_some());
+        assert!(compile(&|d| d.classes[0] = "schema:Country".to_string()).is_some());
+        assert!(compile(&|d| d.targets[0].property = Some("Q5".to_string())).is_some());
+        assert!(compile(&|d| d.targets[1].property = Some("P1".to_string())).is_some());
+        assert!(compile(&|d| d.targets[1].vars = Some(vec!["x".to_string()])).is_some());
+        assert!(compile(&|d| d.targets[1].name = "population".to_string()).is_some());
+        assert!(compile(&|d| d.targets[0].datatype = Some("decimal".to_string())).is_some());
+    }
+}
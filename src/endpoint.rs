@@ -0,0 +1,226 @@
+use crate::error::Error;
+use crate::replay::Fixtures;
+use crate::result_format::ResultFormat;
+use crate::retry::RetryPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use strum_macros::{Display, EnumString};
+
+/// Which family of generated queries an endpoint understands.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, EnumString, Display)]
+pub enum Dialect {
+    #[strum(serialize = "wikidata")]
+    #[serde(rename = "wikidata")]
+    Wikidata,
+    #[strum(serialize = "dbpedia")]
+    #[serde(rename = "dbpedia")]
+    Dbpedia,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// One request is allowed every `interval_ms` on average...
+    pub interval_ms: u64,
+    /// ...with up to `burst` requests sent back to back after a quiet period.
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_burst() -> u32 {
+    1
+}
+
+fn default_max_in_flight() -> usize {
+    1
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Endpoint {
+    /// Used on the command line and as the directory name under `sparql/`.
+    pub name: String,
+    /// The SPARQL endpoint, or `file:` followed by the path of a local
+    /// N-Triples or Turtle extract to query offline.
+    pub uri: String,
+    pub dialect: Dialect,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// Fetch results in LIMIT/OFFSET pages of this many rows.
+    #[serde(default)]
+    pub page_size: Option<usize>,
+    /// The most rows the server returns for one request; a response of this
+    /// size is treated as truncated.
+    #[serde(default)]
+    pub max_rows: Option<usize>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// How many queries may run against the endpoint at the same time.
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    /// The encoding results are requested in.
+    #[serde(default)]
+    pub format: ResultFormat,
+    /// Record responses to fixtures, or replay them instead of sending requests.
+    #[serde(default)]
+    pub fixtures: Option<Fixtures>,
+}
+
+impl Endpoint {
+    /// The local extract the endpoint is answered from, if it is one.
+    pub fn dump(&self) -> Option<&Path> {
+        let path = self.uri.strip_prefix("file:")?;
+        Some(Path::new(path.strip_prefix("//").unwrap_or(path)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EndpointsFile {
+    endpoints: Vec<Endpoint>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EndpointRegistry {
+    endpoints: Vec<Endpoint>,
+}
+
+impl EndpointRegistry {
+    pub fn builtin() -> Self {
+        EndpointRegistry {
+            endpoints: vec![
+                Endpoint {
+                    name: "wikidata".to_string(),
+                    uri: "https://query.wikidata.org/sparql".to_string(),
+                    dialect: Dialect::Wikidata,
+                    headers: BTreeMap::new(),
+                    // WDQS allows five concurrent queries per client.
+                    rate_limit: Some(RateLimit {
+                        interval_ms: 1000,
+                        burst: 5,
+                    }),
+                    page_size: None,
+                    max_rows: None,
+                    retry: RetryPolicy::default(),
+                    max_in_flight: 5,
+                    format: ResultFormat::Json,
+                    fixtures: None,
+                },
+                Endpoint {
+                    name: "dbpedia".to_string(),
+                    uri: "https://dbpedia.org/sparql".to_string(),
+                    dialect: Dialect::Dbpedia,
+                    headers: BTreeMap::new(),
+                    rate_limit: Some(RateLimit {
+                        interval_ms: 1000,
+                        burst: 2,
+                    }),
+                    page_size: Some(10000),
+                    max_rows: Some(10000),
+                    retry: RetryPolicy::default(),
+                    max_in_flight: 2,
+                    format: ResultFormat::Json,
+                    fixtures: None,
+                },
+            ],
+        }
+    }
+
+    /// Built-in endpoints plus the ones declared in a JSON file of the form
+    /// `{"endpoints": [{"name": ..., "uri": ..., "dialect": ...}]}`.
+    /// An entry with the name of a built-in endpoint replaces it.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let text = fs::read_to_string(path).map_err(|e| Error::io(path, e))?;
+        let mut registry = Self::builtin();
+        registry.extend_from_str(&text)?;
+        Ok(registry)
+    }
+
+    fn extend_from_str(&mut self, text: &str) -> Result<(), Error> {
+        let file: EndpointsFile = serde_json::from_str(text)
+            .map_err(|e| Error::Config(format!("invalid endpoints file: {}", e)))?;
+        for endpoint in file.endpoints {
+            self.insert(endpoint);
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, endpoint: Endpoint) {
+        match self.endpoints.iter_mut().find(|e| e.name == endpoint.name) {
+            Some(existing) => *existing = endpoint,
+            None => self.endpoints.push(endpoint),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Endpoint> {
+        self.endpoints.iter().find(|e| e.name == name)
+    }
+
+    /// Looks up `name` and checks that it speaks the dialect the caller generates queries for.
+    pub fn resolve(&self, name: &str, dialect: Dialect) -> Result<&Endpoint, Error> {
+        match self.get(name) {
+            Some(endpoint) if endpoint.dialect == dialect => Ok(endpoint),
+            Some(endpoint) => Err(Error::Config(format!(
+                "endpoint {} uses the {} dialect, expected {}",
+                name, endpoint.dialect, dialect
+            ))),
+            None => Err(Error::UnknownEndpoint(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_endpoints() {
+        let registry = EndpointRegistry::builtin();
+        let wikidata = registry.resolve("wikidata", Dialect::Wikidata).unwrap();
+        assert_eq!(wikidata.uri, "https://query.wikidata.org/sparql");
+        assert!(registry.resolve("wikidata", Dialect::Dbpedia).is_err());
+        assert!(registry.resolve("hoge", Dialect::Wikidata).is_err());
+    }
+
+    #[test]
+    fn test_extend_from_str() {
+        let mut registry = EndpointRegistry::builtin();
+        registry
+            .extend_from_str(
+                r#"{"endpoints": [
+                    {"name": "local", "uri": "http://localhost:7878/query", "dialect": "wikidata",
+                     "headers": {"Authorization": "Basic Zm9vOmJhcg=="},
+                     "rate_limit": {"interval_ms": 500}},
+                    {"name": "offline", "uri": "file:extract/wikidata.nt", "dialect": "wikidata"},
+                    {"name": "dbpedia", "uri": "http://localhost:8890/sparql", "dialect": "dbpedia",
+                     "retry": {"max_attempts": 2}, "format": "xml"}
+                ]}"#,
+            )
+            .unwrap();
+
+        let local = registry.resolve("local", Dialect::Wikidata).unwrap();
+        assert_eq!(local.headers["Authorization"], "Basic Zm9vOmJhcg==");
+        assert_eq!(
+            local.rate_limit,
+            Some(RateLimit {
+                interval_ms: 500,
+                burst: 1
+            })
+        );
+        assert_eq!(local.max_in_flight, 1);
+        assert_eq!(local.dump(), None);
+
+        let offline = registry.resolve("offline", Dialect::Wikidata).unwrap();
+        assert_eq!(offline.dump(), Some(Path::new("extract/wikidata.nt")));
+
+        let dbpedia = registry.get("dbpedia").unwrap();
+        assert_eq!(dbpedia.uri, "http://localhost:8890/sparql");
+        assert_eq!(dbpedia.rate_limit, None);
+        assert_eq!(dbpedia.page_size, None);
+        assert_eq!(dbpedia.retry.max_attempts, 2);
+        assert_eq!(dbpedia.format, ResultFormat::Xml);
+        assert_eq!(local.format, ResultFormat::Json);
+        assert_eq!(dbpedia.retry.budget_ms, RetryPolicy::default().budget_ms);
+    }
+}
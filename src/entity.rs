@@ -0,0 +1,150 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+static WIKIDATA_ENTITY: &str = "http://www.wikidata.org/entity/";
+static DBPEDIA_RESOURCE: &str = "http://dbpedia.org/resource/";
+
+thread_local! {
+    /// Whether `EntityId`s are serialized as their IRI; see `with_full_iris`.
+    static FULL_IRIS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` with every `EntityId` it serializes written as its full IRI when
+/// `full_iris` is set, instead of its compact form.
+pub fn with_full_iris<T>(full_iris: bool, f: impl FnOnce() -> T) -> T {
+    let previous = FULL_IRIS.replace(full_iris);
+    let result = f();
+    FULL_IRIS.set(previous);
+    result
+}
+
+/// An entity of merged output, written in its compact form: `Q172579` for
+/// Wikidata items and `dbr:Battle_of_Cannae` for DBpedia resources.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub enum EntityId {
+    /// The number of a Wikidata QID.
+    Wikidata(u64),
+    /// The name of a DBpedia resource.
+    Dbpedia(String),
+}
+
+impl EntityId {
+    /// The entity `iri` names, if it is the canonical IRI of one.
+    pub fn from_iri(iri: &str) -> Option<Self> {
+        if let Some(qid) = iri.strip_prefix(WIKIDATA_ENTITY) {
+            return parse_qid(qid).map(EntityId::Wikidata);
+        }
+        let name = iri.strip_prefix(DBPEDIA_RESOURCE)?;
+        (!name.is_empty()).then(|| EntityId::Dbpedia(name.to_string()))
+    }
+
+    pub fn iri(&self) -> String {
+        match self {
+            EntityId::Wikidata(number) => format!("{}Q{}", WIKIDATA_ENTITY, number),
+            EntityId::Dbpedia(name) => format!("{}{}", DBPEDIA_RESOURCE, name),
+        }
+    }
+}
+
+fn parse_qid(qid: &str) -> Option<u64> {
+    let number = qid.strip_prefix('Q')?;
+    // `Q01` is not the IRI of `Q1`, nor of any other item.
+    if number.is_empty() || number.starts_with('0') || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    number.parse().ok()
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityId::Wikidata(number) => write!(f, "Q{}", number),
+            EntityId::Dbpedia(name) => write!(f, "dbr:{}", name),
+        }
+    }
+}
+
+impl FromStr for EntityId {
+    type Err = String;
+
+    /// Accepts the compact form as well as the full IRI.
+    fn from_str(text: &str) -> Result<Self, String> {
+        if let Some(name) = text.strip_prefix("dbr:") {
+            if !name.is_empty() {
+                return Ok(EntityId::Dbpedia(name.to_string()));
+            }
+        } else if let Some(number) = parse_qid(text) {
+            return Ok(EntityId::Wikidata(number));
+        } else if let Some(id) = Self::from_iri(text) {
+            return Ok(id);
+        }
+        Err(format!("not an entity id: {}", text))
+    }
+}
+
+impl Serialize for EntityId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if FULL_IRIS.get() {
+            serializer.serialize_str(&self.iri())
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EntityId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_iri() {
+        let rome = "http://www.wikidata.org/entity/Q220";
+        assert_eq!(EntityId::from_iri(rome), Some(EntityId::Wikidata(220)));
+        assert_eq!(
+            EntityId::from_iri("http://dbpedia.org/resource/Battle_of_Cannae"),
+            Some(EntityId::Dbpedia("Battle_of_Cannae".to_string()))
+        );
+        for iri in [
+            "http://www.wikidata.org/entity/P31",
+            "http://www.wikidata.org/entity/Q01",
+            "http://commons.wikimedia.org/wiki/Special:FilePath/Flag.svg",
+        ] {
+            assert_eq!(EntityId::from_iri(iri), None);
+        }
+    }
+
+    #[test]
+    fn test_with_full_iris() {
+        let rome = EntityId::Wikidata(220);
+        assert_eq!(serde_json::to_string(&rome).unwrap(), "\"Q220\"");
+        let json = with_full_iris(true, || serde_json::to_string(&rome).unwrap());
+        assert_eq!(json, "\"http://www.wikidata.org/entity/Q220\"");
+        assert_eq!(serde_json::from_str::<EntityId>(&json).unwrap(), rome);
+        assert_eq!(serde_json::to_string(&rome).unwrap(), "\"Q220\"");
+    }
+
+    #[test]
+    fn test_resolve() {
+        for text in ["Q220", "dbr:Rome", "http://www.wikidata.org/entity/Q220"] {
+            let id: EntityId = text.parse().unwrap();
+            assert_eq!(EntityId::from_iri(&id.iri()), Some(id.clone()));
+            let json = serde_json::to_string(&id).unwrap();
+            assert_eq!(serde_json::from_str::<EntityId>(&json).unwrap(), id);
+        }
+        assert_eq!(
+            "Q220".parse::<EntityId>().unwrap().iri(),
+            "http://www.wikidata.org/entity/Q220"
+        );
+        assert!("Q".parse::<EntityId>().is_err());
+        assert!("Rome".parse::<EntityId>().is_err());
+    }
+}
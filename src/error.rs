@@ -0,0 +1,171 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Everything that can make a command fail.
+///
+/// Each variant maps to its own process exit code so that scripts can tell a
+/// mistyped category from an endpoint outage:
+///
+/// | code | meaning                                          |
+/// |------|--------------------------------------------------|
+/// | 2    | unknown category, target or endpoint; bad config |
+/// | 3    | network failure (connect, timeout)               |
+/// | 4    | non-success HTTP status; query rejected          |
+/// | 5    | response or file could not be decoded            |
+/// | 6    | result truncated by the server                   |
+/// | 7    | file system error                                |
+///
+/// A bulk run that fails partially exits with the code of its first failure.
+#[derive(Debug)]
+pub enum Error {
+    UnknownCategory(String),
+    UnknownTarget {
+        category: String,
+        target: String,
+    },
+    UnknownEndpoint(String),
+    Config(String),
+    Network {
+        endpoint: String,
+        message: String,
+    },
+    HttpStatus {
+        endpoint: String,
+        status: u16,
+    },
+    /// A query a local store could not evaluate.
+    Query {
+        endpoint: String,
+        message: String,
+    },
+    Decode {
+        source: String,
+        message: String,
+    },
+    Truncated {
+        endpoint: String,
+        rows: usize,
+    },
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A retried request that never succeeded; `last` is the final failure.
+    GaveUp {
+        attempts: u32,
+        last: Box<Error>,
+    },
+    /// Some targets of a bulk run failed; `first` is the earliest in run order.
+    Incomplete {
+        failed: usize,
+        total: usize,
+        first: Box<Error>,
+    },
+}
+
+impl Error {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::UnknownCategory(_)
+            | Error::UnknownTarget { .. }
+            | Error::UnknownEndpoint(_)
+            | Error::Config(_) => 2,
+            Error::Network { .. } => 3,
+            Error::HttpStatus { .. } | Error::Query { .. } => 4,
+            Error::Decode { .. } => 5,
+            Error::Truncated { .. } => 6,
+            Error::Io { .. } => 7,
+            Error::GaveUp { last, .. } => last.exit_code(),
+            Error::Incomplete { first, .. } => first.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownCategory(category) => write!(f, "unknown category: {}", category),
+            Error::UnknownTarget { category, target } => {
+                write!(f, "unknown target for {}: {}", category, target)
+            }
+            Error::UnknownEndpoint(name) => write!(f, "unknown endpoint: {}", name),
+            Error::Config(message) => write!(f, "{}", message),
+            Error::Network { endpoint, message } => {
+                write!(f, "{}: request error: {}", endpoint, message)
+            }
+            Error::HttpStatus { endpoint, status } => write!(f, "{}: HTTP {}", endpoint, status),
+            Error::Query { endpoint, message } => {
+                write!(f, "{}: cannot evaluate query: {}", endpoint, message)
+            }
+            Error::Decode { source, message } => {
+                write!(f, "{}: cannot decode SPARQL results: {}", source, message)
+            }
+            Error::Truncated { endpoint, rows } => write!(
+                f,
+                "{} returned {} rows, the server limit; the result is truncated",
+                endpoint, rows
+            ),
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::GaveUp { attempts, last } => {
+                write!(f, "{} (gave up after {} attempts)", last, attempts)
+            }
+            Error::Incomplete {
+                failed,
+                total,
+                first,
+            } => write!(
+                f,
+                "{} of {} targets failed, first: {}",
+                failed, total, first
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Prints the error of a failed command and turns the outcome into the process exit code.
+pub fn exit(result: Result<(), Error>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let bad_target = Error::UnknownTarget {
+            category: "country".to_string(),
+            target: "hoge".to_string(),
+        };
+        let outage = Error::GaveUp {
+            attempts: 6,
+            last: Box::new(Error::HttpStatus {
+                endpoint: "wikidata".to_string(),
+                status: 503,
+            }),
+        };
+        assert_eq!(bad_target.exit_code(), 2);
+        assert_eq!(outage.exit_code(), 4);
+        assert_eq!(
+            outage.to_string(),
+            "wikidata: HTTP 503 (gave up after 6 attempts)"
+        );
+    }
+}
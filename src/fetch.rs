@@ -0,0 +1,636 @@
+use crate::endpoint::Endpoint;
+use crate::error::Error;
+use crate::pagination::{is_truncated, paged_query};
+use crate::provenance::{sidecar_path, Provenance, RequestRecord};
+use crate::query_category::Categories;
+use crate::rate_limit::TokenBucket;
+use crate::replay::{Fixture, FixtureMode};
+use crate::result_format::{ReadError, ResultHandler};
+use crate::retry::{is_retryable_status, parse_retry_after};
+use crate::store::LocalStore;
+use crate::wikidata_queries::sparql_types::{Head, Object};
+use reqwest::blocking::Client;
+use reqwest::header::{ACCEPT, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, path::PathBuf};
+use urlencoding::encode;
+
+/// Identifies the tool and who to contact about it, as the Wikidata Query
+/// Service User-Agent policy asks of every client.
+const USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("CARGO_PKG_AUTHORS"),
+    ") reqwest/0.11"
+);
+
+/// A failed attempt, and whether it is worth repeating.
+struct Failure {
+    error: Error,
+    retryable: bool,
+    retry_after: Option<Duration>,
+}
+
+/// Sends the queries of one endpoint, sharing an HTTP client and a rate limiter
+/// between every request, including those issued from worker threads.
+/// An endpoint with a local extract is queried in memory instead.
+pub struct Fetcher<'a> {
+    client: Client,
+    endpoint: &'a Endpoint,
+    categories: &'a Categories,
+    limiter: Option<TokenBucket>,
+    store: Option<LocalStore>,
+}
+
+impl<'a> Fetcher<'a> {
+    pub fn new(endpoint: &'a Endpoint, categories: &'a Categories) -> Result<Self, Error> {
+        let store = match endpoint.dump() {
+            Some(path) => Some(LocalStore::load(&endpoint.name, path)?),
+            None => None,
+        };
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .build()
+            .map_err(|e| Error::Network {
+                endpoint: endpoint.name.clone(),
+                message: format!("could not build the HTTP client: {}", e),
+            })?;
+        Ok(Fetcher {
+            client,
+            endpoint,
+            categories,
+            limiter: endpoint.rate_limit.as_ref().map(TokenBucket::new),
+            store,
+        })
+    }
+
+    /// Fetches one target into `{data_dir}/sparql/{endpoint}/{category}/{target}.json`
+    /// and returns the number of rows written. Bindings are written through to
+    /// disk as they are read, so the response is never held in memory.
+    pub fn fetch(&self, category: &str, target: &str, data_dir: &str) -> Result<usize, Error> {
+        let endpoint = self.endpoint;
+        let sparql = self.categories.gen_query(category, target)?;
+
+        let directory = format!("{}/sparql/{}/{}", data_dir, endpoint.name, category);
+        let mut path = PathBuf::from(directory);
+        fs::create_dir_all(&path).map_err(|e| Error::io(&path, e))?;
+        path.push(format!("{}.json", target));
+
+        let mut writer = ResultWriter::create(&path)?;
+        let requests = self.run_query(&sparql, &mut writer)?;
+        let expected = self.categories.get(category)?.output_vars(target);
+        let vars = writer
+            .head
+            .as_ref()
+            .map(|head| &head.vars[..])
+            .unwrap_or_default();
+        let missing: Vec<_> = expected
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|var| !vars.contains(var))
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::Decode {
+                source: endpoint.name.clone(),
+                message: format!("response does not bind ?{}", missing.join(" ?")),
+            });
+        }
+        let rows = writer.finish()?;
+
+        let provenance = Provenance::new(category, target, endpoint, &sparql, requests);
+        let sidecar = sidecar_path(&path);
+        let json = serde_json::to_string_pretty(&provenance).unwrap();
+        fs::write(&sidecar, json).map_err(|e| Error::io(&sidecar, e))?;
+        Ok(rows)
+    }
+
+    /// Fetches every `(category, target)` pair with at most `max_in_flight`
+    /// queries running at the same time, reporting each outcome to `on_done`.
+    /// Every job is attempted; if any fails, the earliest failure in job order is returned.
+    pub fn fetch_all<F>(
+        &self,
+        jobs: &[(String, String)],
+        data_dir: &str,
+        max_in_flight: usize,
+        on_done: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&str, &str, &Result<usize, Error>) + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let failures = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..max_in_flight.clamp(1, jobs.len().max(1)) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some((category, target)) = jobs.get(index) else {
+                        break;
+                    };
+                    println!("{} -> {}", category, target);
+                    let result = self.fetch(category, target, data_dir);
+                    if let Err(e) = &result {
+                        println!("Error: {}", e);
+                    }
+                    on_done(category, target, &result);
+                    if let Err(e) = result {
+                        failures.lock().unwrap().push((index, e));
+                    }
+                });
+            }
+        });
+
+        let mut failures = failures.into_inner().unwrap();
+        failures.sort_by_key(|(index, _)| *index);
+        let failed = failures.len();
+        match failures.into_iter().next() {
+            Some((_, first)) => Err(Error::Incomplete {
+                failed,
+                total: jobs.len(),
+                first: Box::new(first),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs `sparql` into `writer`, paging through the results when the endpoint
+    /// has a page size and the query projects variables to order the pages by,
+    /// and refuses results the server has silently cut off at `max_rows`.
+    /// Returns a record of every request that was written.
+    pub fn run_query(
+        &self,
+        sparql: &str,
+        writer: &mut ResultWriter,
+    ) -> Result<Vec<RequestRecord>, Error> {
+        let endpoint = self.endpoint;
+        let Some(page_size) = endpoint.page_size else {
+            return self.run_unpaged(sparql, writer);
+        };
+
+        let mut requests = vec![];
+        let mut offset = 0;
+        loop {
+            let Some(paged) = paged_query(sparql, page_size, offset) else {
+                return self.run_unpaged(sparql, writer);
+            };
+            let record = self.request(&paged, Some(offset), writer)?;
+            let rows = record.rows;
+            requests.push(record);
+            if is_truncated(rows, Some(page_size), endpoint.max_rows) {
+                return Err(Error::Truncated {
+                    endpoint: endpoint.name.clone(),
+                    rows,
+                });
+            }
+            if rows < page_size {
+                break;
+            }
+            offset += page_size;
+        }
+        Ok(requests)
+    }
+
+    fn run_unpaged(
+        &self,
+        sparql: &str,
+        writer: &mut ResultWriter,
+    ) -> Result<Vec<RequestRecord>, Error> {
+        let record = self.request(sparql, None, writer)?;
+        if is_truncated(record.rows, None, self.endpoint.max_rows) {
+            return Err(Error::Truncated {
+                endpoint: self.endpoint.name.clone(),
+                rows: record.rows,
+            });
+        }
+        Ok(vec![record])
+    }
+
+    /// Sends `sparql`, retrying transient failures according to the endpoint's
+    /// retry policy. The bindings of a failed attempt are taken back out of
+    /// `writer` before the next one.
+    fn request(
+        &self,
+        sparql: &str,
+        offset: Option<usize>,
+        writer: &mut ResultWriter,
+    ) -> Result<RequestRecord, Error> {
+        let endpoint = self.endpoint;
+        let policy = &endpoint.retry;
+        let started = Instant::now();
+        let checkpoint = writer.checkpoint()?;
+        let mut attempt = 1;
+        loop {
+            let failure = match self.send(sparql, writer) {
+                Ok((rows, status)) => {
+                    return Ok(RequestRecord {
+                        http_status: status.as_u16(),
+                        attempts: attempt,
+                        rows,
+                        offset,
+                    });
+                }
+                Err(failure) => failure,
+            };
+            writer.rollback(&checkpoint)?;
+            let delay = failure
+                .retry_after
+                .unwrap_or_else(|| policy.backoff(attempt, fastrand::f64()));
+            let exhausted =
+                attempt >= policy.max_attempts || started.elapsed() + delay > policy.budget();
+            if !failure.retryable || exhausted {
+                return Err(if attempt == 1 {
+                    failure.error
+                } else {
+                    Error::GaveUp {
+                        attempts: attempt,
+                        last: Box::new(failure.error),
+                    }
+                });
+            }
+            println!(
+                "{} (attempt {}/{}), retrying in {:.1}s",
+                failure.error,
+                attempt,
+                policy.max_attempts,
+                delay.as_secs_f64()
+            );
+            // The server asked everyone to back off, not just this request.
+            if let (Some(limiter), Some(retry_after)) = (&self.limiter, failure.retry_after) {
+                limiter.pause(retry_after);
+            }
+            thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    /// Sends `sparql` and writes its bindings to `writer`, returning how many
+    /// there were.
+    fn send(
+        &self,
+        sparql: &str,
+        writer: &mut ResultWriter,
+    ) -> Result<(usize, StatusCode), Failure> {
+        let endpoint = self.endpoint;
+        let failure = |error| Failure {
+            error,
+            retryable: false,
+            retry_after: None,
+        };
+        let temp = writer.temp.clone();
+        let write_failure = |e| failure(Error::io(&temp, e));
+        if let Some(store) = &self.store {
+            let value = store.query(sparql).map_err(failure)?;
+            writer.begin(Some(&value.head)).map_err(write_failure)?;
+            for binding in &value.results.bindings {
+                writer.push(binding).map_err(write_failure)?;
+            }
+            writer.end(Some(&value.head), value.boolean);
+            return Ok((value.results.bindings.len(), StatusCode::OK));
+        }
+        let response = match &endpoint.fixtures {
+            Some(fixtures) if fixtures.mode == FixtureMode::Replay => {
+                fixtures.load(sparql).map_err(failure)?.into()
+            }
+            Some(fixtures) => {
+                let mut response = self.send_http(sparql)?;
+                let mut body = String::new();
+                response
+                    .body
+                    .read_to_string(&mut body)
+                    .map_err(|e| self.read_failure(ReadError::Io(e), &temp))?;
+                let fixture = Fixture {
+                    query: sparql.to_string(),
+                    status: response.status,
+                    retry_after: response.retry_after,
+                    body,
+                };
+                fixtures.save(&fixture).map_err(failure)?;
+                fixture.into()
+            }
+            None => self.send_http(sparql)?,
+        };
+
+        let status = StatusCode::from_u16(response.status).map_err(|e| {
+            failure(Error::Decode {
+                source: endpoint.name.clone(),
+                message: e.to_string(),
+            })
+        })?;
+        if !status.is_success() {
+            return Err(Failure {
+                error: Error::HttpStatus {
+                    endpoint: endpoint.name.clone(),
+                    status: status.as_u16(),
+                },
+                retryable: is_retryable_status(status),
+                retry_after: response
+                    .retry_after
+                    .and_then(|value| parse_retry_after(&value, SystemTime::now())),
+            });
+        }
+        let rows = writer.rows;
+        endpoint
+            .format
+            .read(response.body, writer)
+            .map_err(|e| self.read_failure(e, &temp))?;
+        Ok((writer.rows - rows, status))
+    }
+
+    /// A body that could not be read to the end is worth another attempt, one
+    /// that is not valid results is not, and neither is a failure to write
+    /// the results to `temp`.
+    fn read_failure(&self, e: ReadError, temp: &Path) -> Failure {
+        let endpoint = self.endpoint.name.clone();
+        match e {
+            ReadError::Handler(e) => Failure {
+                error: Error::io(temp, e),
+                retryable: false,
+                retry_after: None,
+            },
+            ReadError::Io(e) => Failure {
+                error: Error::Network {
+                    endpoint,
+                    message: e.to_string(),
+                },
+                retryable: true,
+                retry_after: None,
+            },
+            ReadError::Invalid(message) => Failure {
+                error: Error::Decode {
+                    source: endpoint,
+                    message,
+                },
+                retryable: false,
+                retry_after: None,
+            },
+        }
+    }
+
+    /// Sends `sparql` over HTTP and returns the response, whatever its status,
+    /// with its body left to be read.
+    fn send_http(&self, sparql: &str) -> Result<Response, Failure> {
+        let endpoint = self.endpoint;
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire();
+        }
+        let url = format!("{}?query={}", endpoint.uri, encode(sparql));
+        let mut request = self
+            .client
+            .get(url)
+            .header(ACCEPT, endpoint.format.media_type());
+        for (name, value) in &endpoint.headers {
+            request = request.header(name, value);
+        }
+        let network = |e: reqwest::Error| Failure {
+            retryable: e.is_timeout() || e.is_connect() || e.is_request() || e.is_body(),
+            error: Error::Network {
+                endpoint: endpoint.name.clone(),
+                message: e.without_url().to_string(),
+            },
+            retry_after: None,
+        };
+        let response = request.send().map_err(network)?;
+        let status = response.status().as_u16();
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(Response {
+            status,
+            retry_after,
+            body: Box::new(BufReader::new(response)),
+        })
+    }
+}
+
+/// A response whose body has not been read yet.
+struct Response {
+    status: u16,
+    retry_after: Option<String>,
+    body: Box<dyn BufRead>,
+}
+
+impl From<Fixture> for Response {
+    fn from(fixture: Fixture) -> Self {
+        Response {
+            status: fixture.status,
+            retry_after: fixture.retry_after,
+            body: Box::new(Cursor::new(fixture.body.into_bytes())),
+        }
+    }
+}
+
+/// Where a `ResultWriter` was before an attempt, to go back to if it fails.
+pub struct Checkpoint {
+    position: u64,
+    rows: usize,
+    begun: bool,
+}
+
+/// Writes the bindings of one target in the SPARQL JSON results format as they
+/// arrive, to a temporary file that replaces `path` once every page is in.
+/// The head is written before the bindings when the first page starts with it,
+/// and after them otherwise. Dropping an unfinished writer removes the file.
+pub struct ResultWriter {
+    path: PathBuf,
+    temp: PathBuf,
+    file: BufWriter<File>,
+    head: Option<Head>,
+    head_written: bool,
+    boolean: Option<bool>,
+    rows: usize,
+    begun: bool,
+    finished: bool,
+}
+
+impl ResultWriter {
+    pub fn create(path: &Path) -> Result<Self, Error> {
+        let temp = path.with_extension("json.part");
+        let file = File::create(&temp).map_err(|e| Error::io(&temp, e))?;
+        Ok(ResultWriter {
+            path: path.to_path_buf(),
+            temp,
+            file: BufWriter::new(file),
+            head: None,
+            head_written: false,
+            boolean: None,
+            rows: 0,
+            begun: false,
+            finished: false,
+        })
+    }
+
+    fn checkpoint(&mut self) -> Result<Checkpoint, Error> {
+        let position = self
+            .file
+            .stream_position()
+            .map_err(|e| Error::io(&self.temp, e))?;
+        Ok(Checkpoint {
+            position,
+            rows: self.rows,
+            begun: self.begun,
+        })
+    }
+
+    fn rollback(&mut self, checkpoint: &Checkpoint) -> Result<(), Error> {
+        let rollback = |file: &mut BufWriter<File>| {
+            file.flush()?;
+            file.get_ref().set_len(checkpoint.position)?;
+            file.seek(SeekFrom::Start(checkpoint.position))
+        };
+        rollback(&mut self.file).map_err(|e| Error::io(&self.temp, e))?;
+        self.rows = checkpoint.rows;
+        if self.begun && !checkpoint.begun {
+            self.head_written = false;
+        }
+        self.begun = checkpoint.begun;
+        Ok(())
+    }
+
+    /// Opens the document before the first binding of the first page.
+    fn begin(&mut self, head: Option<&Head>) -> io::Result<()> {
+        if self.begun {
+            return Ok(());
+        }
+        write!(self.file, "{{")?;
+        if let Some(head) = head {
+            write!(self.file, "\n  \"head\": {},", indented(head, 2))?;
+            self.head = Some(head.clone());
+            self.head_written = true;
+        }
+        write!(self.file, "\n  \"results\": {{\n    \"bindings\": [")?;
+        self.begun = true;
+        Ok(())
+    }
+
+    fn push(&mut self, binding: &HashMap<String, Object>) -> io::Result<()> {
+        let separator = if self.rows > 0 { "," } else { "" };
+        write!(self.file, "{}\n      {}", separator, indented(binding, 6))?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Takes the head and ASK result of a page once it has been read; only
+    /// those of the first page are kept.
+    fn end(&mut self, head: Option<&Head>, boolean: Option<bool>) {
+        if self.head.is_none() {
+            self.head = head.cloned();
+        }
+        if self.boolean.is_none() {
+            self.boolean = boolean;
+        }
+    }
+
+    /// Closes the document, moves it into place and returns how many bindings
+    /// it holds.
+    fn finish(mut self) -> Result<usize, Error> {
+        let mut close = || {
+            self.begin(None)?;
+            match self.rows {
+                0 => write!(self.file, "]\n  }}")?,
+                _ => write!(self.file, "\n    ]\n  }}")?,
+            }
+            if let (Some(head), false) = (&self.head, self.head_written) {
+                write!(self.file, ",\n  \"head\": {}", indented(head, 2))?;
+            }
+            if let Some(boolean) = self.boolean {
+                write!(self.file, ",\n  \"boolean\": {}", boolean)?;
+            }
+            write!(self.file, "\n}}")?;
+            self.file.flush()
+        };
+        close().map_err(|e| Error::io(&self.temp, e))?;
+        fs::rename(&self.temp, &self.path).map_err(|e| Error::io(&self.path, e))?;
+        self.finished = true;
+        Ok(self.rows)
+    }
+}
+
+/// Writes every page of a response into the one document.
+impl ResultHandler for ResultWriter {
+    fn head(&mut self, head: Head) -> io::Result<()> {
+        match self.begun {
+            true => self.end(Some(&head), None),
+            false => self.begin(Some(&head))?,
+        }
+        Ok(())
+    }
+
+    fn binding(&mut self, binding: HashMap<String, Object>) -> io::Result<()> {
+        self.begin(None)?;
+        self.push(&binding)
+    }
+
+    fn boolean(&mut self, boolean: bool) {
+        self.end(None, Some(boolean));
+    }
+}
+
+impl Drop for ResultWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// `value` as pretty JSON nested `depth` spaces deep.
+fn indented<T: Serialize>(value: &T, depth: usize) -> String {
+    let json = serde_json::to_string_pretty(value).unwrap();
+    json.replace('\n', &format!("\n{}", " ".repeat(depth)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wikidata_queries::sparql_types::SparqlResponse;
+
+    #[test]
+    fn test_writer_rolls_back_failed_attempts() {
+        let dir = std::env::temp_dir().join(format!("tevere_data-fetch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("label.json");
+        let binding = |value: &str| {
+            let object = Object {
+                value: value.to_string(),
+                ..Object::default()
+            };
+            HashMap::from([("label".to_string(), object)])
+        };
+
+        let mut writer = ResultWriter::create(&path).unwrap();
+        let checkpoint = writer.checkpoint().unwrap();
+        writer.begin(None).unwrap();
+        writer.push(&binding("lost")).unwrap();
+        writer.rollback(&checkpoint).unwrap();
+        writer.begin(None).unwrap();
+        writer.push(&binding("Roma")).unwrap();
+        let head = Head {
+            vars: vec!["label".to_string()],
+            link: vec![],
+        };
+        writer.end(Some(&head), None);
+        assert_eq!(writer.finish().unwrap(), 1);
+
+        let text = fs::read_to_string(&path).unwrap();
+        let response: SparqlResponse = serde_json::from_str(&text).unwrap();
+        assert_eq!(response.head.vars, vec!["label"]);
+        assert_eq!(response.results.bindings, vec![binding("Roma")]);
+        assert!(!path.with_extension("json.part").exists());
+
+        drop(ResultWriter::create(&path).unwrap());
+        assert!(!path.with_extension("json.part").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Wikidata writes the globe of coordinates on other bodies as an IRI in front
+/// of the point; Earth coordinates have none, or this one.
+static EARTH: &str = "http://www.wikidata.org/entity/Q2";
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Coordinates {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WktError {
+    /// A point on another globe, e.g. the Moon (Q405).
+    OtherGlobe(String),
+    Invalid(String),
+}
+
+impl fmt::Display for WktError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WktError::OtherGlobe(globe) => write!(f, "not on Earth but on {}", globe),
+            WktError::Invalid(reason) => write!(f, "invalid WKT point: {}", reason),
+        }
+    }
+}
+
+/// Whether a bound value is a WKT literal: Wikidata types them as
+/// `geo:wktLiteral`, DBpedia as `virtrdf:Geometry`.
+pub fn is_wkt_datatype(datatype: &str) -> bool {
+    datatype.ends_with("#wktLiteral") || datatype.ends_with("#Geometry")
+}
+
+/// Parses `Point(lon lat)`, optionally preceded by `<globe>`. WKT puts the
+/// longitude first.
+pub fn parse_wkt_point(literal: &str) -> Result<Coordinates, WktError> {
+    let mut point = literal.trim();
+    if let Some(rest) = point.strip_prefix('<') {
+        let (globe, rest) = rest
+            .split_once('>')
+            .ok_or_else(|| WktError::Invalid("unterminated globe IRI".to_string()))?;
+        if globe != EARTH {
+            return Err(WktError::OtherGlobe(globe.to_string()));
+        }
+        point = rest.trim_start();
+    }
+    let body = point
+        .get(..5)
+        .filter(|keyword| keyword.eq_ignore_ascii_case("point"))
+        .map(|_| point[5..].trim_start())
+        .and_then(|rest| rest.strip_prefix('('))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or_else(|| WktError::Invalid(format!("not a point: {}", literal)))?;
+
+    let numbers: Vec<f64> = body
+        .split_whitespace()
+        .map(|n| n.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|e| WktError::Invalid(format!("{}: {}", literal, e)))?;
+    let [lon, lat] = numbers[..] else {
+        return Err(WktError::Invalid(format!(
+            "expected two coordinates: {}",
+            literal
+        )));
+    };
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(WktError::Invalid(format!("out of range: {}", literal)));
+    }
+    Ok(Coordinates { lat, lon })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_wkt_point() {
+        let rome = Coordinates {
+            lat: 41.89,
+            lon: 12.48,
+        };
+        assert_eq!(parse_wkt_point("Point(12.48 41.89)"), Ok(rome));
+        assert_eq!(parse_wkt_point("POINT(12.48 41.89)"), Ok(rome));
+        assert_eq!(
+            parse_wkt_point("<http://www.wikidata.org/entity/Q2> Point(12.48 41.89)"),
+            Ok(rome)
+        );
+        assert_eq!(
+            parse_wkt_point("<http://www.wikidata.org/entity/Q405> Point(23.47 0.67)"),
+            Err(WktError::OtherGlobe(
+                "http://www.wikidata.org/entity/Q405".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_invalid_wkt() {
+        for literal in [
+            "",
+            "41.89 12.48",
+            "Point(12.48)",
+            "Point(12.48 41.89 3)",
+            "Point(12.48 hoge)",
+            "Point(12.48 141.89)",
+            "LineString(0 0, 1 1)",
+            "<http://www.wikidata.org/entity/Q2 Point(0 0)",
+        ] {
+            assert!(
+                matches!(parse_wkt_point(literal), Err(WktError::Invalid(_))),
+                "{}",
+                literal
+            );
+        }
+    }
+}
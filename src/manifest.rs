@@ -0,0 +1,155 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Done,
+    Failed,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub status: Status,
+    /// Seconds since the Unix epoch when the fetch finished.
+    pub timestamp: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which targets a bulk run fetches, given what the manifest already records.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RunMode {
+    /// Everything that has not completed yet.
+    Resume,
+    /// Everything, ignoring the manifest.
+    Force,
+    /// Only targets whose last fetch failed.
+    OnlyFailed,
+}
+
+/// Progress of bulk runs against one endpoint, stored as
+/// `{data}/sparql/{endpoint}/manifest.json` and keyed by `category/target`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(skip)]
+    path: PathBuf,
+    pub entries: BTreeMap<String, Entry>,
+}
+
+fn key(category: &str, target: &str) -> String {
+    format!("{}/{}", category, target)
+}
+
+impl Manifest {
+    pub fn load(data_dir: &str, endpoint: &str) -> Result<Self, Error> {
+        let path = PathBuf::from(format!("{}/sparql/{}/manifest.json", data_dir, endpoint));
+        let mut manifest = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str::<Manifest>(&text).map_err(|e| Error::Decode {
+                source: path.display().to_string(),
+                message: e.to_string(),
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => Manifest::default(),
+            Err(e) => return Err(Error::io(&path, e)),
+        };
+        manifest.path = path;
+        Ok(manifest)
+    }
+
+    pub fn should_fetch(&self, category: &str, target: &str, mode: RunMode) -> bool {
+        let status = self.entries.get(&key(category, target)).map(|e| e.status);
+        match mode {
+            RunMode::Force => true,
+            RunMode::Resume => status != Some(Status::Done),
+            RunMode::OnlyFailed => status == Some(Status::Failed),
+        }
+    }
+
+    pub fn record(&mut self, category: &str, target: &str, result: &Result<usize, Error>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = match result {
+            Ok(rows) => Entry {
+                status: Status::Done,
+                timestamp,
+                rows: Some(*rows),
+                error: None,
+            },
+            Err(e) => Entry {
+                status: Status::Failed,
+                timestamp,
+                rows: None,
+                error: Some(e.to_string()),
+            },
+        };
+        self.entries.insert(key(category, target), entry);
+    }
+
+    /// Writes the manifest through a temporary file so that a crash never leaves it half written.
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| Error::io(parent, e))?;
+        }
+        let json = serde_json::to_string_pretty(self).unwrap();
+        let temporary = self.path.with_extension("json.tmp");
+        fs::write(&temporary, json)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .map_err(|e| Error::io(&self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_fetch() {
+        let mut manifest = Manifest::default();
+        manifest.record("country", "inception", &Ok(120));
+        manifest.record(
+            "country",
+            "label",
+            &Err(Error::HttpStatus {
+                endpoint: "wikidata".to_string(),
+                status: 503,
+            }),
+        );
+
+        assert!(!manifest.should_fetch("country", "inception", RunMode::Resume));
+        assert!(manifest.should_fetch("country", "label", RunMode::Resume));
+        assert!(manifest.should_fetch("country", "flag", RunMode::Resume));
+
+        assert!(manifest.should_fetch("country", "inception", RunMode::Force));
+
+        assert!(!manifest.should_fetch("country", "inception", RunMode::OnlyFailed));
+        assert!(manifest.should_fetch("country", "label", RunMode::OnlyFailed));
+        assert!(!manifest.should_fetch("country", "flag", RunMode::OnlyFailed));
+    }
+
+    #[test]
+    fn test_record_overwrites() {
+        let mut manifest = Manifest::default();
+        manifest.record(
+            "war",
+            "label",
+            &Err(Error::HttpStatus {
+                endpoint: "wikidata".to_string(),
+                status: 429,
+            }),
+        );
+        manifest.record("war", "label", &Ok(3));
+        let entry = &manifest.entries["war/label"];
+        assert_eq!(entry.status, Status::Done);
+        assert_eq!(entry.rows, Some(3));
+        assert_eq!(entry.error, None);
+    }
+}
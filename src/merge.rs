@@ -0,0 +1,1046 @@
+use crate::date::HistoricalDate;
+use crate::endpoint::Dialect;
+use crate::entity::{with_full_iris, EntityId};
+use crate::error::Error;
+use crate::geo::WktError;
+use crate::model::{Field, Merged, Model, Rank, Reference};
+use crate::provenance::{is_sidecar, sidecar_path, Provenance};
+use crate::query_category::{Categories, QueryCategory, Statement, ValueVar};
+use crate::result_format::{ReadError, ResultFormat};
+use crate::wikidata_queries::sparql_types::{Object, TermType};
+use oxigraph::model::vocab::xsd;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::{fs, path::PathBuf};
+use strum_macros::{Display, EnumString};
+
+/// Target linking an entity of another source to its Wikidata IRI; its
+/// bindings are `?{category} ?sameAs`.
+pub static SAME_AS: &str = "sameAs";
+
+/// Which values merge keeps by the rank of their statement. Values without a
+/// rank, such as labels and DBpedia values, count as normal.
+#[derive(Debug, PartialEq, Clone, Copy, Default, EnumString, Display)]
+pub enum RankPolicy {
+    /// The preferred values of a field where it has any, and its normal values
+    /// otherwise; what `wdt:` returns. Values dated by time qualifiers, like
+    /// the capitals of a country over time, are a history rather than
+    /// alternatives, so all but their deprecated ones are kept.
+    #[default]
+    #[strum(serialize = "preferred")]
+    Preferred,
+    /// Every value but the deprecated ones.
+    #[strum(serialize = "not-deprecated")]
+    NotDeprecated,
+    /// Every value; deprecated ones are flagged by being kept apart under
+    /// `deprecated`.
+    #[strum(serialize = "all")]
+    All,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MergeOptions {
+    /// Write entities as their full IRI instead of their compact `EntityId`.
+    pub full_iris: bool,
+    pub rank_policy: RankPolicy,
+}
+
+/// Merges the results of one category into the model it is written as; see
+/// `QueryCategory::merger`.
+pub type Merger = fn(&Definitions, &Sources, &str, &MergeOptions) -> Result<(), Error>;
+
+/// The `Merger` writing a category as `M`.
+pub fn merger<M: Model>() -> Merger {
+    merge_into::<M>
+}
+
+/// One category as every dialect defines it. Results fetched from an endpoint
+/// are read with the definition of its dialect.
+pub struct Definitions<'a> {
+    definitions: Vec<(Dialect, &'a dyn QueryCategory)>,
+}
+
+impl<'a> Definitions<'a> {
+    /// The categories of every dialect grouped by name, in the order of
+    /// `dialects` and of their categories.
+    pub fn group(dialects: &'a [(Dialect, Categories)]) -> Vec<Self> {
+        let mut grouped: Vec<Definitions> = vec![];
+        for (dialect, categories) in dialects {
+            for category in categories.iter() {
+                let definition = (*dialect, category);
+                match grouped.iter_mut().find(|c| c.name() == category.name()) {
+                    Some(known) => known.definitions.push(definition),
+                    None => grouped.push(Definitions {
+                        definitions: vec![definition],
+                    }),
+                }
+            }
+        }
+        grouped
+    }
+
+    pub fn name(&self) -> &str {
+        self.definitions[0].1.name()
+    }
+
+    /// The model of the first definition; every dialect is merged into it.
+    fn merger(&self) -> Merger {
+        self.definitions[0].1.merger()
+    }
+
+    /// The definition the result file `path` of `target` is read with: the
+    /// one of the dialect its provenance records, or else the first one that
+    /// knows the target.
+    fn definition(&self, path: &Path, target: &str) -> Option<(&'a dyn QueryCategory, Statement)> {
+        let dialect = fs::read_to_string(sidecar_path(path))
+            .ok()
+            .and_then(|text| serde_json::from_str::<Provenance>(&text).ok())
+            .map(|provenance| provenance.dialect);
+        let known = |(_, category): &&(Dialect, &'a dyn QueryCategory)| {
+            category.statement(target).is_some()
+        };
+        let (_, category) = self
+            .definitions
+            .iter()
+            .filter(known)
+            .find(|(d, _)| Some(*d) == dialect)
+            .or_else(|| self.definitions.iter().find(known))?;
+        Some((*category, category.statement(target)?))
+    }
+}
+
+/// The raw results one category is merged from.
+pub struct Sources<'a> {
+    /// `(endpoint, directory)` of every endpoint with results for the category.
+    directories: Vec<(String, PathBuf)>,
+    /// The Wikidata IRI of every entity of another source linked to one, from
+    /// the `sameAs` targets of every category.
+    same_as: &'a HashMap<String, String>,
+}
+
+/// Combines the raw results of `category` fetched from every endpoint under
+/// `{output}/sparql/{endpoint}/{category}` (or only from `endpoints` when given)
+/// into `{output}/result/{category}.json`, one object per entity.
+///
+/// Entities of other sources are folded into their Wikidata entity through the
+/// `sameAs` targets of the `known` categories, both where they are the subject
+/// and where they are the value of a field, and each object records under
+/// `sources` which endpoints every field came from. Entities are keyed by their
+/// compact `EntityId` unless `options.full_iris` is set. Values are kept by the
+/// rank of their statement as `options.rank_policy` says.
+pub fn merge(
+    category: &Definitions,
+    known: &[Definitions],
+    output: &str,
+    endpoints: &[String],
+    options: &MergeOptions,
+) -> Result<(), Error> {
+    let sparql_directory = PathBuf::from(format!("{}/sparql", output));
+    let directories = source_directories(&sparql_directory, category.name(), endpoints)?;
+    if directories.is_empty() {
+        return Err(Error::Config(format!(
+            "no fetched results for {} under {}",
+            category.name(),
+            sparql_directory.display()
+        )));
+    }
+    let same_as = load_same_as(&sparql_directory, known, endpoints)?;
+    let sources = Sources {
+        directories,
+        same_as: &same_as,
+    };
+    // Values are written as JSON as they are merged, so entities have to be
+    // written as IRIs from the start.
+    with_full_iris(options.full_iris, || {
+        category.merger()(category, &sources, output, options)
+    })
+}
+
+/// Merges every category that has fetched results and returns how many were merged.
+pub fn merge_all(
+    categories: &[Definitions],
+    output: &str,
+    endpoints: &[String],
+    options: &MergeOptions,
+) -> Result<usize, Error> {
+    let sparql_directory = PathBuf::from(format!("{}/sparql", output));
+    let same_as = load_same_as(&sparql_directory, categories, endpoints)?;
+    let mut merged = 0;
+    for category in categories {
+        let directories = source_directories(&sparql_directory, category.name(), endpoints)?;
+        if !directories.is_empty() {
+            println!("{}", category.name());
+            let sources = Sources {
+                directories,
+                same_as: &same_as,
+            };
+            with_full_iris(options.full_iris, || {
+                category.merger()(category, &sources, output, options)
+            })?;
+            merged += 1;
+        }
+    }
+    Ok(merged)
+}
+
+/// The `sameAs` links fetched for any of `categories`, from the IRI of the
+/// other source to the Wikidata IRI.
+fn load_same_as(
+    sparql_directory: &Path,
+    categories: &[Definitions],
+    endpoints: &[String],
+) -> Result<HashMap<String, String>, Error> {
+    let mut same_as = HashMap::new();
+    for category in categories {
+        for (_, directory) in source_directories(sparql_directory, category.name(), endpoints)? {
+            let path = directory.join(format!("{}.json", SAME_AS));
+            if !path.is_file() {
+                continue;
+            }
+            let Some((definition, _)) = category.definition(&path, SAME_AS) else {
+                continue;
+            };
+            let subject = definition.subject();
+            read_bindings(&path, |binding| {
+                if let (Some(entity), Some(other)) = (binding.get(subject), binding.get(SAME_AS)) {
+                    same_as.insert(entity.value.clone(), other.value.clone());
+                }
+            })?;
+        }
+    }
+    Ok(same_as)
+}
+
+/// Merges into the model `M` of `category`.
+fn merge_into<M: Model>(
+    category: &Definitions,
+    sources: &Sources,
+    output: &str,
+    options: &MergeOptions,
+) -> Result<(), Error> {
+    let mut result = Merging::<M> {
+        entities: BTreeMap::new(),
+        policy: options.rank_policy,
+        preferred: HashSet::new(),
+        skipped: Skipped::default(),
+    };
+    for (source, directory) in &sources.directories {
+        for path in result_files(directory)? {
+            let target = path.file_stem().unwrap_or_default().to_string_lossy();
+            let Some((definition, statement)) = category.definition(&path, &target) else {
+                result.skipped.invalid.push(format!(
+                    "{}: no definition of {}",
+                    path.display(),
+                    target
+                ));
+                continue;
+            };
+            let context = Context {
+                subject: definition.subject(),
+                source,
+                statement,
+                same_as: sources.same_as,
+            };
+            read_bindings(&path, |binding| result.merge_binding(&binding, &context))?;
+        }
+    }
+    let Merging {
+        entities: result,
+        skipped,
+        ..
+    } = result;
+    if skipped.other_globes > 0 {
+        eprintln!(
+            "{}: skipped {} coordinates on other globes",
+            category.name(),
+            skipped.other_globes
+        );
+    }
+    for message in &skipped.invalid {
+        eprintln!("{}: {}", category.name(), message);
+    }
+
+    let output_directory = PathBuf::from(format!("{}/result", output));
+    let output_path = PathBuf::from(format!("{}/result/{}.json", output, category.name()));
+
+    fs::create_dir_all(&output_directory).map_err(|e| Error::io(&output_directory, e))?;
+
+    let json = serde_json::to_string_pretty(&result).unwrap();
+
+    fs::write(&output_path, json).map_err(|e| Error::io(&output_path, e))
+}
+
+/// `(endpoint, directory)` for every endpoint that has results for `category`, by endpoint name.
+fn source_directories(
+    sparql_directory: &Path,
+    category: &str,
+    endpoints: &[String],
+) -> Result<Vec<(String, PathBuf)>, Error> {
+    let entries = fs::read_dir(sparql_directory).map_err(|e| Error::io(sparql_directory, e))?;
+    let mut sources = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io(sparql_directory, e))?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let directory = entry.path().join(category);
+        if !directory.is_dir() || !(endpoints.is_empty() || endpoints.contains(&name)) {
+            continue;
+        }
+        sources.push((name, directory));
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+/// Raw result files of one category directory, in a stable order, leaving out
+/// the provenance sidecars and results still being fetched.
+fn result_files(directory: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = fs::read_dir(directory).map_err(|e| Error::io(directory, e))?;
+    let mut files = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io(directory, e))?.path();
+        let is_json = path
+            .extension()
+            .is_some_and(|extension| extension == "json");
+        if path.is_file() && is_json && !is_sidecar(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Hands every binding of a raw result file to `merge` as it is read.
+fn read_bindings(path: &Path, mut merge: impl FnMut(HashMap<String, Object>)) -> Result<(), Error> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    let mut handler = |binding| {
+        merge(binding);
+        Ok(())
+    };
+    ResultFormat::Json
+        .read(BufReader::new(file), &mut handler)
+        .map_err(|e| match e {
+            ReadError::Io(e) | ReadError::Handler(e) => Error::io(path, e),
+            ReadError::Invalid(message) => Error::Decode {
+                source: path.display().to_string(),
+                message,
+            },
+        })
+}
+
+/// Values left out of the merged output, reported once the category is written.
+#[derive(Debug, Default)]
+struct Skipped {
+    other_globes: usize,
+    invalid: Vec<String>,
+}
+
+/// What every binding of one result file is merged with.
+struct Context<'a> {
+    subject: &'a str,
+    source: &'a str,
+    /// How the bindings of the target are read.
+    statement: Statement,
+    same_as: &'a HashMap<String, String>,
+}
+
+impl Context<'_> {
+    /// The Wikidata IRI `iri` is linked to through `sameAs`, or `iri` itself.
+    fn canonical<'a>(&'a self, iri: &'a str) -> &'a str {
+        self.same_as.get(iri).map_or(iri, String::as_str)
+    }
+
+    /// The entity `iri` stands for in the merged output, if it names one.
+    fn entity(&self, iri: &str) -> Option<EntityId> {
+        EntityId::from_iri(self.canonical(iri))
+    }
+}
+
+/// A value read from one binding.
+struct Claim {
+    entity: EntityId,
+    field: String,
+    value: Field,
+    rank: Rank,
+    /// Whether the value is one of a history dated by time qualifiers.
+    dated: bool,
+    reference: Option<Reference>,
+    source: String,
+}
+
+/// The output of one category as its bindings are merged into it.
+struct Merging<M> {
+    entities: BTreeMap<EntityId, Merged<M>>,
+    policy: RankPolicy,
+    /// Fields of an entity that have a preferred value; `RankPolicy::Preferred`
+    /// keeps only the preferred values of those.
+    preferred: HashSet<(EntityId, String)>,
+    skipped: Skipped,
+}
+
+impl<M: Model> Merging<M> {
+    fn merge_binding(&mut self, binding: &HashMap<String, Object>, context: &Context) {
+        let Some(entity) = binding.get(context.subject) else {
+            return;
+        };
+        let Some(key) = context.entity(&entity.value) else {
+            self.skipped
+                .invalid
+                .push(format!("{}: not an entity", entity.value));
+            return;
+        };
+        self.entities.entry(key.clone()).or_default();
+        match merge_field(binding, context) {
+            Ok(Some((field, value))) => {
+                let rank = (context.statement.rank.as_ref())
+                    .and_then(|rank| binding.get(rank))
+                    .and_then(|rank| Rank::from_iri(&rank.value))
+                    .unwrap_or_default();
+                let reference = reference(binding, &context.statement, &value);
+                self.add_claim(Claim {
+                    entity: key,
+                    field,
+                    value,
+                    rank,
+                    dated: context.statement.is_dated(),
+                    reference,
+                    source: context.source.to_string(),
+                });
+            }
+            Ok(None) => {}
+            Err(WktError::OtherGlobe(_)) => self.skipped.other_globes += 1,
+            Err(e) => self.skipped.invalid.push(format!("{}: {}", key, e)),
+        }
+    }
+
+    /// Adds `claim` to its entity if the rank policy keeps it. Under
+    /// `RankPolicy::Preferred`, the first preferred value of a field drops the
+    /// values added to it before.
+    fn add_claim(&mut self, claim: Claim) {
+        let merged = self.entities.entry(claim.entity.clone()).or_default();
+        match (self.policy, claim.rank) {
+            (RankPolicy::Preferred, Rank::Preferred | Rank::Normal) if claim.dated => {}
+            (RankPolicy::Preferred, Rank::Preferred) => {
+                let field = (claim.entity.clone(), claim.field.clone());
+                if self.preferred.insert(field) {
+                    merged.entity.clear(&claim.field);
+                    merged.references.remove(&claim.field);
+                    merged.sources.remove(&claim.field);
+                }
+            }
+            (RankPolicy::Preferred, Rank::Normal) => {
+                let field = (claim.entity.clone(), claim.field.clone());
+                if self.preferred.contains(&field) {
+                    return;
+                }
+            }
+            (RankPolicy::All, _) | (_, Rank::Normal | Rank::Preferred) => {}
+            (_, Rank::Deprecated) => return,
+        }
+        match claim.value {
+            // The sameAs link itself is kept the other way round: the Wikidata
+            // entity lists the IRIs that were folded into it.
+            Field::Entity(other) if claim.field == SAME_AS => {
+                if !merged.same_as.contains(&other) {
+                    merged.same_as.push(other);
+                }
+            }
+            value => {
+                let model = match claim.rank {
+                    Rank::Deprecated => merged.deprecated.get_or_insert_with(M::default),
+                    _ => &mut merged.entity,
+                };
+                if let Err(e) = model.insert(&claim.field, value) {
+                    self.skipped
+                        .invalid
+                        .push(format!("{}: {}: {}", claim.entity, claim.field, e));
+                    return;
+                }
+            }
+        }
+        if let Some(reference) = claim.reference {
+            merged.add_reference(&claim.field, reference);
+        }
+        merged.add_source(&claim.field, &claim.source);
+    }
+}
+
+/// The reference bound with the value of `statement`, if the target was
+/// fetched with its references and the statement has one.
+fn reference(
+    binding: &HashMap<String, Object>,
+    statement: &Statement,
+    value: &Field,
+) -> Option<Reference> {
+    let vars = statement.references.as_ref()?;
+    let reference = Reference {
+        value: value.to_json(),
+        stated_in: binding
+            .get(&vars.stated_in)
+            .and_then(|stated_in| EntityId::from_iri(&stated_in.value)),
+        reference_url: binding
+            .get(&vars.reference_url)
+            .map(|reference_url| reference_url.value.clone()),
+        retrieved: HistoricalDate::from_binding(
+            binding,
+            &ValueVar::plain(&vars.retrieved).typed(xsd::DATE_TIME.as_str()),
+        ),
+    };
+    let is_empty = reference.stated_in.is_none()
+        && reference.reference_url.is_none()
+        && reference.retrieved.is_none();
+    (!is_empty).then_some(reference)
+}
+
+/// The field `binding` fills and its value.
+fn merge_field(
+    binding: &HashMap<String, Object>,
+    context: &Context,
+) -> Result<Option<(String, Field)>, WktError> {
+    let statement = &context.statement;
+    let field = &statement.value.name;
+    let Some(object) = binding.get(field) else {
+        return Ok(None);
+    };
+    // The sameAs link is kept the other way round: the subject is the entity
+    // of the other source, and the Wikidata entity lists it.
+    if *field == SAME_AS {
+        let other = EntityId::from_iri(&binding[context.subject].value);
+        return Ok(other.map(|other| (SAME_AS.to_string(), Field::Entity(other))));
+    }
+    // A language-tagged text, with its tag either bound to the language
+    // variable, as `labelled` does, or carried by the literal itself.
+    let language = statement.language.as_ref().and_then(|language| {
+        binding
+            .get(language)
+            .map(|language| &language.value)
+            .or(object.lang.as_ref())
+    });
+    let value = match language {
+        Some(language) => Field::Text {
+            language: language.clone(),
+            text: object.value.clone(),
+        },
+        None => value_field(binding, &statement.value, context)?,
+    };
+    if statement.qualifiers.is_empty() {
+        return Ok(Some((field.clone(), value)));
+    }
+    let mut qualifiers = BTreeMap::new();
+    for qualifier in &statement.qualifiers {
+        if binding.contains_key(&qualifier.name) {
+            let value = value_field(binding, qualifier, context)?;
+            qualifiers.insert(qualifier.name.clone(), value);
+        }
+    }
+    let value = Field::Qualified {
+        value: Box::new(value),
+        qualifiers,
+    };
+    Ok(Some((field.clone(), value)))
+}
+
+/// The value bound to `value`: coordinates, a `HistoricalDate`, a number, or
+/// else the bound string, with entities of other sources replaced by the
+/// Wikidata entity they are linked to. Dates that do not parse are kept
+/// as the string.
+fn value_field(
+    binding: &HashMap<String, Object>,
+    value: &ValueVar,
+    context: &Context,
+) -> Result<Field, WktError> {
+    let object = value.with_datatype(&binding[&value.name]);
+    if let Some(coordinates) = object.as_wkt_point() {
+        return Ok(Field::Coordinates(coordinates?));
+    }
+    if let Some(date) = HistoricalDate::from_binding(binding, value) {
+        return Ok(Field::Date(date));
+    }
+    Ok(match object.as_decimal() {
+        Some(number) => Field::Number(number),
+        None if object.r#type == TermType::Uri => match context.entity(&object.value) {
+            Some(id) => Field::Entity(id),
+            None => Field::Term(object.value.clone()),
+        },
+        None => Field::Term(object.value.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geo::Coordinates;
+    use crate::model::{Battle, Country, Record};
+    use crate::wikidata_queries::country::qualified_capital;
+    use crate::wikidata_queries::{
+        ranked, ranked_coordinates, ranked_time, reference_vars, time_var,
+    };
+    use oxigraph::model::vocab::geosparql;
+    use serde_json::json;
+
+    fn binding(pairs: &[(&str, &str)]) -> HashMap<String, Object> {
+        pairs
+            .iter()
+            .map(|(key, value)| {
+                let r#type = if value.starts_with("http://") {
+                    TermType::Uri
+                } else {
+                    TermType::Literal
+                };
+                let object = Object {
+                    value: value.to_string(),
+                    r#type,
+                    ..Object::default()
+                };
+                (key.to_string(), object)
+            })
+            .collect()
+    }
+
+    fn plain(value: &str) -> Statement {
+        Statement::new(ValueVar::plain(value))
+    }
+
+    fn id(iri: &str) -> EntityId {
+        EntityId::from_iri(iri).unwrap()
+    }
+
+    /// Merges every `(source, statement, binding)` of `subject` like
+    /// `merge_into` merges result files.
+    fn merge_bindings<M: Model>(
+        subject: &str,
+        same_as: &HashMap<String, String>,
+        bindings: &[(&str, Statement, HashMap<String, Object>)],
+    ) -> (BTreeMap<EntityId, Merged<M>>, Skipped) {
+        merge_ranked(subject, same_as, RankPolicy::Preferred, bindings)
+    }
+
+    fn merge_ranked<M: Model>(
+        subject: &str,
+        same_as: &HashMap<String, String>,
+        policy: RankPolicy,
+        bindings: &[(&str, Statement, HashMap<String, Object>)],
+    ) -> (BTreeMap<EntityId, Merged<M>>, Skipped) {
+        let mut result = Merging {
+            entities: BTreeMap::new(),
+            policy,
+            preferred: HashSet::new(),
+            skipped: Skipped::default(),
+        };
+        for (source, statement, binding) in bindings {
+            let context = Context {
+                subject,
+                source,
+                statement: statement.clone(),
+                same_as,
+            };
+            result.merge_binding(binding, &context);
+        }
+        (result.entities, result.skipped)
+    }
+
+    #[test]
+    fn test_results_are_read_by_their_dialect() {
+        let dir = std::env::temp_dir().join(format!("tevere_data-merge-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dialects = [
+            (Dialect::Wikidata, Categories::builtin(Dialect::Wikidata)),
+            (Dialect::Dbpedia, Categories::builtin(Dialect::Dbpedia)),
+        ];
+        let grouped = Definitions::group(&dialects);
+        let capital = grouped.iter().find(|c| c.name() == "capital").unwrap();
+        assert_eq!(capital.definitions.len(), 2);
+
+        let path = dir.join("coordinates.json");
+        let statement = |path: &Path, target| capital.definition(path, target).map(|d| d.1);
+        assert_eq!(statement(&path, "coordinates"), Some(ranked_coordinates()));
+        let registry = crate::endpoint::EndpointRegistry::builtin();
+        let endpoint = registry.get("dbpedia").unwrap();
+        let provenance = Provenance::new("capital", "coordinates", endpoint, "select ...", vec![]);
+        fs::write(
+            sidecar_path(&path),
+            serde_json::to_string(&provenance).unwrap(),
+        )
+        .unwrap();
+        let wkt = geosparql::WKT_LITERAL.as_str();
+        assert_eq!(
+            statement(&path, "coordinates"),
+            Some(plain("coordinates").typed(wkt))
+        );
+        // Only DBpedia links capitals to Wikidata.
+        assert_eq!(
+            statement(&dir.join("sameAs.json"), SAME_AS),
+            Some(plain(SAME_AS))
+        );
+        assert_eq!(statement(&path, "hoge"), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_dates() {
+        let wd = "http://www.wikidata.org/entity/Q12560";
+        let inception = |value| {
+            let binding = binding(&[
+                ("country", wd),
+                ("inception", value),
+                ("inceptionPrecision", "9"),
+                (
+                    "inceptionCalendar",
+                    "http://www.wikidata.org/entity/Q1985786",
+                ),
+            ]);
+            ("wikidata", ranked_time("inception"), binding)
+        };
+        let same_as = HashMap::new();
+        let (result, skipped) = merge_bindings::<Country>(
+            "country",
+            &same_as,
+            &[
+                inception("1299-01-01T00:00:00Z"),
+                inception("1299-13-01T00:00:00Z"),
+            ],
+        );
+        assert_eq!(
+            serde_json::to_value(&result[&id(wd)].entity.inception).unwrap(),
+            json!([{
+                "value": "1299",
+                "precision": "year",
+                "calendar": "julian",
+                "earliest": "1299-01-08",
+                "latest": "1300-01-07",
+            }])
+        );
+        assert_eq!(
+            skipped.invalid,
+            vec![format!(
+                "{}: inception: expected a date, got \"1299-13-01T00:00:00Z\"",
+                id(wd)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_merge_coordinates() {
+        let wd = "http://www.wikidata.org/entity/Q48314";
+        let bindings: Vec<_> = [
+            "Point(16.13 41.31)",
+            "<http://www.wikidata.org/entity/Q405> Point(23.47 0.67)",
+            "Point(16.13)",
+        ]
+        .iter()
+        .map(|coordinates| {
+            let mut binding = binding(&[("battle", wd), ("coordinates", coordinates)]);
+            binding.get_mut("coordinates").unwrap().datatype =
+                Some("http://www.opengis.net/ont/geosparql#wktLiteral".to_string());
+            ("wikidata", plain("coordinates"), binding)
+        })
+        .collect();
+        let same_as = HashMap::new();
+        let (result, skipped) = merge_bindings::<Battle>("battle", &same_as, &bindings);
+        assert_eq!(
+            result[&id(wd)].entity.coordinates,
+            vec![Coordinates {
+                lat: 41.31,
+                lon: 16.13
+            }]
+        );
+        assert_eq!(skipped.other_globes, 1);
+        assert_eq!(skipped.invalid.len(), 1);
+        assert!(skipped.invalid[0].starts_with("Q48314"));
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let wd = "http://www.wikidata.org/entity/Q48314";
+        let dbr = "http://dbpedia.org/resource/Battle_of_Cannae";
+        let same_as = HashMap::from([(dbr.to_string(), wd.to_string())]);
+
+        let label = || Statement::text("label", "language");
+        let bindings = [
+            (
+                "wikidata",
+                label(),
+                binding(&[("battle", wd), ("label", "Cannae"), ("language", "en")]),
+            ),
+            (
+                "dbpedia",
+                Statement::text("abstract", "language"),
+                binding(&[
+                    ("battle", dbr),
+                    ("abstract", "The battle"),
+                    ("language", "en"),
+                ]),
+            ),
+            (
+                "dbpedia",
+                label(),
+                binding(&[("battle", dbr), ("label", "Cannae"), ("language", "en")]),
+            ),
+            (
+                "dbpedia",
+                plain(SAME_AS),
+                binding(&[("battle", dbr), ("sameAs", wd)]),
+            ),
+        ];
+        let (result, _) = merge_bindings::<Battle>("battle", &same_as, &bindings);
+
+        assert_eq!(
+            with_full_iris(true, || serde_json::to_value(&result).unwrap()),
+            json!({
+                wd: {
+                    "label": {"en": "Cannae"},
+                    "abstract": {"en": "The battle"},
+                    "sameAs": [dbr],
+                    "sources": {
+                        "label": ["wikidata", "dbpedia"],
+                        "abstract": ["dbpedia"],
+                        "sameAs": ["dbpedia"],
+                    },
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_compact_iris() {
+        let wd = "http://www.wikidata.org/entity/Q220";
+        let dbr = "http://dbpedia.org/resource/Rome";
+        let same_as = HashMap::from([(dbr.to_string(), wd.to_string())]);
+        let country = binding(&[
+            ("city", wd),
+            ("country", "http://www.wikidata.org/entity/Q1747689"),
+        ]);
+        let bindings = [
+            ("wikidata", plain("country"), country),
+            (
+                "dbpedia",
+                plain(SAME_AS),
+                binding(&[("city", dbr), ("sameAs", wd)]),
+            ),
+            (
+                "dbpedia",
+                plain("website"),
+                binding(&[("city", dbr), ("website", "http://www.comune.roma.it/")]),
+            ),
+        ];
+
+        let (result, _) = merge_bindings::<Record>("city", &same_as, &bindings);
+        let result = serde_json::to_value(&result).unwrap();
+        assert_eq!(result["Q220"]["country"], json!(["Q1747689"]));
+        assert_eq!(result["Q220"]["sameAs"], json!(["dbr:Rome"]));
+        assert_eq!(
+            result["Q220"]["website"],
+            json!(["http://www.comune.roma.it/"])
+        );
+    }
+
+    #[test]
+    fn test_merge_linked_values() {
+        let wd = |id| format!("http://www.wikidata.org/entity/{}", id);
+        let dbr = |name| format!("http://dbpedia.org/resource/{}", name);
+        let same_as = HashMap::from([(dbr("Italy"), wd("Q38")), (dbr("Rome"), wd("Q220"))]);
+        let capital =
+            |country: &str, capital: &str| binding(&[("country", country), ("capital", capital)]);
+        let bindings = [
+            (
+                "wikidata",
+                ranked("capital"),
+                capital(&wd("Q38"), &wd("Q220")),
+            ),
+            (
+                "dbpedia",
+                plain("capital"),
+                capital(&dbr("Italy"), &dbr("Rome")),
+            ),
+        ];
+        let (result, _) = merge_bindings::<Country>("country", &same_as, &bindings);
+        let country = &result[&EntityId::Wikidata(38)];
+        assert_eq!(
+            country.entity.capital.keys().collect::<Vec<_>>(),
+            vec![&EntityId::Wikidata(220)]
+        );
+        assert_eq!(country.sources["capital"], vec!["wikidata", "dbpedia"]);
+    }
+
+    #[test]
+    fn test_merge_qualified_statement() {
+        let wd = "http://www.wikidata.org/entity/Q220";
+        let statement = plain("population")
+            .qualifier(time_var("pointInTime"))
+            .qualifier(ValueVar::plain("determinationMethod"));
+        let bindings: Vec<_> = ["2011", "2020"]
+            .iter()
+            .map(|year| {
+                let mut binding = binding(&[
+                    ("city", wd),
+                    ("population", "2783809"),
+                    ("pointInTime", year),
+                ]);
+                binding.get_mut("pointInTime").unwrap().datatype =
+                    Some("http://www.w3.org/2001/XMLSchema#gYear".to_string());
+                ("wikidata", statement.clone(), binding)
+            })
+            .collect();
+        let same_as = HashMap::new();
+        let (result, _) = merge_bindings::<Record>("city", &same_as, &bindings);
+        let result = serde_json::to_value(&result).unwrap();
+        let population = result["Q220"]["population"].as_array().unwrap();
+        assert_eq!(population.len(), 2);
+        assert_eq!(population[1]["value"], json!("2783809"));
+        assert_eq!(population[1]["pointInTime"]["value"], json!("2020"));
+        assert!(population[1].get("determinationMethod").is_none());
+    }
+
+    #[test]
+    fn test_merge_references() {
+        let wd = "http://www.wikidata.org/entity/Q38";
+        let reference = |url: &str| {
+            let binding = binding(&[
+                ("country", wd),
+                ("capital", "http://www.wikidata.org/entity/Q220"),
+                ("capitalStatedIn", "http://www.wikidata.org/entity/Q36578"),
+                ("capitalReferenceUrl", url),
+            ]);
+            let statement = ranked("capital").references(reference_vars("capital"));
+            ("wikidata", statement, binding)
+        };
+        let bindings = [
+            reference("https://example.org/a"),
+            reference("https://example.org/a"),
+            reference("https://example.org/b"),
+        ];
+        let same_as = HashMap::new();
+        let (result, _) = merge_bindings::<Country>("country", &same_as, &bindings);
+        let references = &result[&id(wd)].references["capital"];
+        assert_eq!(references.len(), 2);
+        assert_eq!(
+            references[0],
+            Reference {
+                value: json!("Q220"),
+                stated_in: Some(EntityId::Wikidata(36578)),
+                reference_url: Some("https://example.org/a".to_string()),
+                retrieved: None,
+            }
+        );
+
+        let bindings = [(
+            "wikidata",
+            ranked_time("inception"),
+            binding(&[("country", wd), ("inception", "1861")]),
+        )];
+        let (result, _) = merge_bindings::<Country>("country", &same_as, &bindings);
+        assert!(result[&id(wd)].references.is_empty());
+    }
+
+    #[test]
+    fn test_merge_typed_literals() {
+        let wd = "http://www.wikidata.org/entity/Q220";
+        let mut label = binding(&[("city", wd), ("label", "Roma")]);
+        label.get_mut("label").unwrap().lang = Some("it".to_string());
+        let mut population = binding(&[("city", wd), ("population", "+2783809")]);
+        population.get_mut("population").unwrap().datatype =
+            Some("http://www.w3.org/2001/XMLSchema#decimal".to_string());
+        let bindings = [
+            ("wikidata", Statement::text("label", "language"), label),
+            ("wikidata", plain("population"), population),
+        ];
+        let same_as = HashMap::new();
+        let (result, _) = merge_bindings::<Record>("city", &same_as, &bindings);
+        let result = serde_json::to_value(&result).unwrap();
+        assert_eq!(result["Q220"]["label"], json!({"it": "Roma"}));
+        assert_eq!(result["Q220"]["population"], json!([2783809]));
+    }
+
+    #[test]
+    fn test_rank_policies() {
+        let wd = "http://www.wikidata.org/entity/Q38";
+        let rank = |capital: &str, rank: &str| {
+            let iri = format!("http://wikiba.se/ontology#{}Rank", rank);
+            let binding = binding(&[("country", wd), ("capital", capital), ("capitalRank", &iri)]);
+            ("wikidata", ranked("capital"), binding)
+        };
+        let bindings = [
+            rank("http://www.wikidata.org/entity/Q220", "Preferred"),
+            rank("http://www.wikidata.org/entity/Q490", "Normal"),
+            rank("http://www.wikidata.org/entity/Q2634", "Deprecated"),
+        ];
+        let capitals = |policy| {
+            let same_as = HashMap::new();
+            let (result, _) = merge_ranked::<Country>("country", &same_as, policy, &bindings);
+            let merged = &result[&id(wd)];
+            let deprecated = merged.deprecated.as_ref().map(|d| d.capital.len());
+            (
+                merged.entity.capital.keys().cloned().collect::<Vec<_>>(),
+                deprecated,
+            )
+        };
+        assert_eq!(
+            capitals(RankPolicy::Preferred),
+            (vec![EntityId::Wikidata(220)], None)
+        );
+        assert_eq!(
+            capitals(RankPolicy::NotDeprecated),
+            (vec![EntityId::Wikidata(220), EntityId::Wikidata(490)], None)
+        );
+        assert_eq!(
+            capitals(RankPolicy::All),
+            (
+                vec![EntityId::Wikidata(220), EntityId::Wikidata(490)],
+                Some(1)
+            )
+        );
+        // A preferred value read after normal ones still replaces them.
+        let reversed: Vec<_> = bindings.iter().rev().cloned().collect();
+        let same_as = HashMap::new();
+        let (result, _) =
+            merge_ranked::<Country>("country", &same_as, RankPolicy::Preferred, &reversed);
+        assert_eq!(
+            result[&id(wd)].entity.capital.keys().collect::<Vec<_>>(),
+            vec![&EntityId::Wikidata(220)]
+        );
+        assert_eq!("not-deprecated".parse(), Ok(RankPolicy::NotDeprecated));
+    }
+
+    #[test]
+    fn test_preferred_keeps_capital_history() {
+        let wd = |id: &str| format!("http://www.wikidata.org/entity/{}", id);
+        let capital = |capital: &str, rank: &str, span: &[(&str, &str)]| {
+            let mut pairs = vec![
+                ("country".to_string(), wd("Q38")),
+                ("capital".to_string(), wd(capital)),
+                (
+                    "capitalRank".to_string(),
+                    format!("http://wikiba.se/ontology#{}Rank", rank),
+                ),
+            ];
+            for (qualifier, year) in span {
+                let time = format!("{}-01-01T00:00:00Z", year);
+                pairs.push((qualifier.to_string(), time));
+                pairs.push((format!("{}Precision", qualifier), "9".to_string()));
+                pairs.push((format!("{}Calendar", qualifier), wd("Q1985727")));
+            }
+            let pairs: Vec<_> = pairs
+                .iter()
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .collect();
+            ("wikidata", qualified_capital(), binding(&pairs))
+        };
+        let bindings = [
+            capital(
+                "Q495",
+                "Normal",
+                &[("startTime", "1861"), ("endTime", "1865")],
+            ),
+            capital("Q220", "Preferred", &[("startTime", "1871")]),
+        ];
+        let same_as = HashMap::new();
+        let (result, _) =
+            merge_ranked::<Country>("country", &same_as, RankPolicy::default(), &bindings);
+        let result = serde_json::to_value(&result).unwrap();
+        let capitals = &result["Q38"]["capital"];
+        assert_eq!(capitals["Q220"][0]["start_time"]["value"], "1871");
+        assert_eq!(capitals["Q495"][0]["start_time"]["value"], "1861");
+        assert_eq!(capitals["Q495"][0]["end_time"]["value"], "1865");
+    }
+}
@@ -0,0 +1,676 @@
+use crate::date::HistoricalDate;
+use crate::entity::EntityId;
+use crate::geo::Coordinates;
+use crate::query_builder::Prefix;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// One value merged from a binding, before it is added to a field of a model.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Field {
+    /// A language-tagged text such as a label or an abstract.
+    Text {
+        language: String,
+        text: String,
+    },
+    Date(HistoricalDate),
+    Coordinates(Coordinates),
+    /// A numeric literal such as a quantity.
+    Number(f64),
+    /// A Wikidata item or a DBpedia resource.
+    Entity(EntityId),
+    /// Any other IRI or bound string.
+    Term(String),
+    /// The value of a statement with the qualifiers bound with it.
+    Qualified {
+        value: Box<Field>,
+        qualifiers: BTreeMap<String, Field>,
+    },
+}
+
+impl Field {
+    fn describe(&self) -> String {
+        match self {
+            Field::Text { .. } => "a text".to_string(),
+            Field::Date(_) => "a date".to_string(),
+            Field::Coordinates(_) => "coordinates".to_string(),
+            Field::Number(number) => number.to_string(),
+            Field::Entity(id) => id.to_string(),
+            Field::Term(term) => format!("{:?}", term),
+            Field::Qualified { value, .. } => format!("{} with qualifiers", value.describe()),
+        }
+    }
+
+    /// How a field no model declares is written: a statement as its value
+    /// next to its qualifiers, e.g. `{"value": "1000", "pointInTime": {...}}`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Field::Text { language, text } => json!({"language": language, "text": text}),
+            Field::Date(date) => json!(date),
+            Field::Coordinates(coordinates) => json!(coordinates),
+            // Integral quantities are written as integers, e.g. `2783809`, not `2783809.0`.
+            Field::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                json!(*number as i64)
+            }
+            Field::Number(number) => json!(number),
+            Field::Entity(id) => json!(id),
+            Field::Term(term) => json!(term),
+            Field::Qualified { value, qualifiers } => {
+                let mut object = json!({"value": value.to_json()});
+                for (qualifier, value) in qualifiers {
+                    object[qualifier] = value.to_json();
+                }
+                object
+            }
+        }
+    }
+
+    fn mismatch<T>(&self, expected: &str) -> Result<T, String> {
+        Err(format!("expected {}, got {}", expected, self.describe()))
+    }
+}
+
+/// The merged output of one category: merge adds every field it reads from
+/// the bindings to a default model, and exporters read models back.
+pub trait Model: Default + Serialize {
+    /// Adds `value` to `field`. Fields the model does not declare are kept as
+    /// they are in its `extra` fields.
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String>;
+
+    /// Drops every value of `field`.
+    fn clear(&mut self, field: &str);
+}
+
+/// `wikibase:rank` of the statement a value comes from.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Rank {
+    Preferred,
+    #[default]
+    Normal,
+    Deprecated,
+}
+
+impl Rank {
+    pub fn from_iri(iri: &str) -> Option<Self> {
+        match iri.strip_prefix(Prefix::Wikibase.iri())? {
+            "PreferredRank" => Some(Rank::Preferred),
+            "NormalRank" => Some(Rank::Normal),
+            "DeprecatedRank" => Some(Rank::Deprecated),
+            _ => None,
+        }
+    }
+}
+
+/// An entity of any category as written to `result/{category}.json`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Merged<M> {
+    #[serde(flatten)]
+    pub entity: M,
+    /// Entities of other sources that were folded into this one.
+    #[serde(rename = "sameAs", default, skip_serializing_if = "Vec::is_empty")]
+    pub same_as: Vec<EntityId>,
+    /// Values of deprecated statements, only kept by `RankPolicy::All`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<M>,
+    /// The references of the values of each field, when they were fetched.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub references: BTreeMap<String, Vec<Reference>>,
+    /// The endpoints each field came from.
+    #[serde(default)]
+    pub sources: BTreeMap<String, Vec<String>>,
+}
+
+/// One `prov:wasDerivedFrom` reference of a statement.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reference {
+    /// The value the statement gives, as it is written to its field.
+    pub value: Value,
+    /// The work the value is stated in (P248).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stated_in: Option<EntityId>,
+    /// P854.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_url: Option<String>,
+    /// When the reference URL was retrieved (P813).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retrieved: Option<HistoricalDate>,
+}
+
+impl<M> Merged<M> {
+    pub fn add_reference(&mut self, field: &str, reference: Reference) {
+        let references = self.references.entry(field.to_string()).or_default();
+        push(references, reference);
+    }
+
+    pub fn add_source(&mut self, field: &str, source: &str) {
+        let sources = self.sources.entry(field.to_string()).or_default();
+        if !sources.iter().any(|s| s == source) {
+            sources.push(source.to_string());
+        }
+    }
+}
+
+/// One period in which a city was the capital, from the qualifiers of a
+/// capital statement. Either end may be open: a span with a start but no end
+/// lasts until an unknown date, or to this day.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct CapitalSpan {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub point_in_time: Option<HistoricalDate>,
+}
+
+impl CapitalSpan {
+    /// The span of the `startTime`, `endTime` and `pointInTime` qualifiers, or
+    /// `None` if the statement has none of them.
+    fn from_qualifiers(qualifiers: &BTreeMap<String, Field>) -> Option<Self> {
+        let date = |qualifier: &str| match qualifiers.get(qualifier) {
+            Some(Field::Date(date)) => Some(*date),
+            _ => None,
+        };
+        let span = CapitalSpan {
+            start_time: date("startTime"),
+            end_time: date("endTime"),
+            point_in_time: date("pointInTime"),
+        };
+        (span != CapitalSpan::default()).then_some(span)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Country {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inception: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dissolution: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coordinates: Vec<Coordinates>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub capital: BTreeMap<EntityId, Vec<CapitalSpan>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flag: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for Country {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "inception" => push_date(&mut self.inception, value),
+            "dissolution" => push_date(&mut self.dissolution, value),
+            "coordinates" => push_coordinates(&mut self.coordinates, value),
+            "capital" => insert_capital(&mut self.capital, value),
+            "flag" => push_term(&mut self.flag, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "inception" => self.inception.clear(),
+            "dissolution" => self.dissolution.clear(),
+            "coordinates" => self.coordinates.clear(),
+            "capital" => self.capital.clear(),
+            "flag" => self.flag.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+/// States, city-states and principalities that are not countries; they are
+/// described by the same fields.
+pub type State = Country;
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct War {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub start_date: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub end_date: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coordinates: Vec<Coordinates>,
+    /// Participants that are people.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub person: Vec<EntityId>,
+    /// Participants that are countries.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub country: Vec<EntityId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for War {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "startDate" => push_date(&mut self.start_date, value),
+            "endDate" => push_date(&mut self.end_date, value),
+            "coordinates" => push_coordinates(&mut self.coordinates, value),
+            "person" => push_entity(&mut self.person, value),
+            "country" => push_entity(&mut self.country, value),
+            "image" => push_term(&mut self.image, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "startDate" => self.start_date.clear(),
+            "endDate" => self.end_date.clear(),
+            "coordinates" => self.coordinates.clear(),
+            "person" => self.person.clear(),
+            "country" => self.country.clear(),
+            "image" => self.image.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Battle {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub r#abstract: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub point_in_time: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coordinates: Vec<Coordinates>,
+    /// The wars and campaigns the battle is part of.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub part_of: Vec<EntityId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub person: Vec<EntityId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub country: Vec<EntityId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub image: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for Battle {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "abstract" => insert_text(&mut self.r#abstract, value),
+            "pointInTime" => push_date(&mut self.point_in_time, value),
+            "coordinates" => push_coordinates(&mut self.coordinates, value),
+            "partOf" => push_entity(&mut self.part_of, value),
+            "person" => push_entity(&mut self.person, value),
+            "country" => push_entity(&mut self.country, value),
+            "image" => push_term(&mut self.image, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "abstract" => self.r#abstract.clear(),
+            "pointInTime" => self.point_in_time.clear(),
+            "coordinates" => self.coordinates.clear(),
+            "partOf" => self.part_of.clear(),
+            "person" => self.person.clear(),
+            "country" => self.country.clear(),
+            "image" => self.image.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct League {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inception: Vec<HistoricalDate>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dissolution: Vec<HistoricalDate>,
+    /// Members and constituents of the league.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<EntityId>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flag: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for League {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "inception" => push_date(&mut self.inception, value),
+            "dissolution" => push_date(&mut self.dissolution, value),
+            "state" => push_entity(&mut self.state, value),
+            "flag" => push_term(&mut self.flag, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "inception" => self.inception.clear(),
+            "dissolution" => self.dissolution.clear(),
+            "state" => self.state.clear(),
+            "flag" => self.flag.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LeagueMember {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coordinates: Vec<Coordinates>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flag: Vec<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for LeagueMember {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "coordinates" => push_coordinates(&mut self.coordinates, value),
+            "flag" => push_term(&mut self.flag, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "coordinates" => self.coordinates.clear(),
+            "flag" => self.flag.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+/// A capital of some country, keyed like the `capital` field of `Country`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capital {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coordinates: Vec<Coordinates>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+impl Model for Capital {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        match field {
+            "label" => insert_text(&mut self.label, value),
+            "coordinates" => push_coordinates(&mut self.coordinates, value),
+            _ => insert_extra(&mut self.extra, field, value),
+        }
+    }
+
+    fn clear(&mut self, field: &str) {
+        match field {
+            "label" => self.label.clear(),
+            "coordinates" => self.coordinates.clear(),
+            _ => {
+                self.extra.remove(field);
+            }
+        }
+    }
+}
+
+/// An entity of a category without a model of its own, e.g. one loaded from
+/// a definition file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, Value>,
+}
+
+impl Model for Record {
+    fn insert(&mut self, field: &str, value: Field) -> Result<(), String> {
+        insert_extra(&mut self.fields, field, value)
+    }
+
+    fn clear(&mut self, field: &str) {
+        self.fields.remove(field);
+    }
+}
+
+fn push<T: PartialEq>(values: &mut Vec<T>, value: T) {
+    // The same value may come from more than one source.
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
+
+fn push_date(values: &mut Vec<HistoricalDate>, value: Field) -> Result<(), String> {
+    match value {
+        Field::Date(date) => {
+            push(values, date);
+            Ok(())
+        }
+        value => value.mismatch("a date"),
+    }
+}
+
+fn push_coordinates(values: &mut Vec<Coordinates>, value: Field) -> Result<(), String> {
+    match value {
+        Field::Coordinates(coordinates) => {
+            push(values, coordinates);
+            Ok(())
+        }
+        value => value.mismatch("coordinates"),
+    }
+}
+
+fn push_term(values: &mut Vec<String>, value: Field) -> Result<(), String> {
+    match value {
+        Field::Term(term) => {
+            push(values, term);
+            Ok(())
+        }
+        value => value.mismatch("a term"),
+    }
+}
+
+fn push_entity(values: &mut Vec<EntityId>, value: Field) -> Result<(), String> {
+    match value {
+        Field::Entity(id) => {
+            push(values, id);
+            Ok(())
+        }
+        value => value.mismatch("an entity"),
+    }
+}
+
+fn insert_text(texts: &mut BTreeMap<String, String>, value: Field) -> Result<(), String> {
+    match value {
+        Field::Text { language, text } => {
+            texts.insert(language, text);
+            Ok(())
+        }
+        value => value.mismatch("a text"),
+    }
+}
+
+/// Adds a span to the spans of its capital; the same city may have been the
+/// capital in more than one period.
+fn insert_capital(
+    capitals: &mut BTreeMap<EntityId, Vec<CapitalSpan>>,
+    value: Field,
+) -> Result<(), String> {
+    let (capital, span) = match value {
+        Field::Qualified { value, qualifiers } => match *value {
+            Field::Entity(capital) => (capital, CapitalSpan::from_qualifiers(&qualifiers)),
+            value => return value.mismatch("a capital"),
+        },
+        Field::Entity(capital) => (capital, None),
+        value => return value.mismatch("a capital"),
+    };
+    let spans = capitals.entry(capital).or_default();
+    if let Some(span) = span {
+        push(spans, span);
+    }
+    Ok(())
+}
+
+/// Keeps a field no model declares: texts by language and everything else
+/// as a list of values.
+fn insert_extra(
+    extra: &mut BTreeMap<String, Value>,
+    field: &str,
+    value: Field,
+) -> Result<(), String> {
+    let entry = extra.entry(field.to_string()).or_insert(Value::Null);
+    if let Field::Text { language, text } = value {
+        if !entry.is_object() {
+            *entry = json!({});
+        }
+        entry[language] = json!(text);
+        return Ok(());
+    }
+    if !entry.is_array() {
+        *entry = json!([]);
+    }
+    if let Some(values) = entry.as_array_mut() {
+        push(values, value.to_json());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::{Calendar, Precision};
+
+    #[test]
+    fn test_insert_typed_fields() {
+        let date = HistoricalDate::parse("-0216-08-02", Precision::Day, Calendar::Julian).unwrap();
+        let mut battle = Merged::<Battle>::default();
+        for (field, value) in [
+            ("pointInTime", Field::Date(date)),
+            ("partOf", Field::Entity(EntityId::Wikidata(6343))),
+            ("partOf", Field::Entity(EntityId::Wikidata(6343))),
+            (
+                "label",
+                Field::Text {
+                    language: "en".to_string(),
+                    text: "Battle of Cannae".to_string(),
+                },
+            ),
+            ("casualties", Field::Term("50000".to_string())),
+        ] {
+            battle.entity.insert(field, value).unwrap();
+            battle.add_source(field, "wikidata");
+        }
+        assert_eq!(battle.entity.point_in_time, vec![date]);
+        assert_eq!(battle.entity.part_of, vec![EntityId::Wikidata(6343)]);
+        assert_eq!(
+            battle
+                .entity
+                .insert("pointInTime", Field::Term("hoge".to_string())),
+            Err("expected a date, got \"hoge\"".to_string())
+        );
+
+        let json = serde_json::to_value(&battle).unwrap();
+        assert_eq!(json["partOf"], json!(["Q6343"]));
+        assert_eq!(json["casualties"], json!(["50000"]));
+        assert_eq!(json["sources"]["label"], json!(["wikidata"]));
+        assert!(json.get("image").is_none());
+        assert_eq!(
+            serde_json::from_value::<Merged<Battle>>(json).unwrap(),
+            battle
+        );
+    }
+
+    #[test]
+    fn test_record_keeps_every_field() {
+        let year = HistoricalDate::parse("2020", Precision::Year, Calendar::Gregorian).unwrap();
+        let population = Field::Qualified {
+            value: Box::new(Field::Term("2783809".to_string())),
+            qualifiers: BTreeMap::from([("pointInTime".to_string(), Field::Date(year))]),
+        };
+        let mut record = Record::default();
+        record.insert("population", population).unwrap();
+        record
+            .insert("motto", Field::Term("SPQR".to_string()))
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            json!({
+                "population": [{"value": "2783809", "pointInTime": year}],
+                "motto": ["SPQR"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_capital_from_qualifiers() {
+        let year = |value: &str| HistoricalDate::parse(value, Precision::Year, Calendar::Gregorian);
+        let capital = |qualifiers: &[(&str, &str)]| Field::Qualified {
+            value: Box::new(Field::Entity(EntityId::Wikidata(220))),
+            qualifiers: qualifiers
+                .iter()
+                .map(|(qualifier, value)| {
+                    (qualifier.to_string(), Field::Date(year(value).unwrap()))
+                })
+                .collect(),
+        };
+        let mut country = Country::default();
+        for qualifiers in [
+            &[("startTime", "-0752"), ("endTime", "0330")][..],
+            &[("startTime", "1871")],
+            &[("startTime", "1871")],
+        ] {
+            country.insert("capital", capital(qualifiers)).unwrap();
+        }
+        let spans = &country.capital[&EntityId::Wikidata(220)];
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].start_time, year("-0752"));
+        assert_eq!(spans[0].end_time, year("0330"));
+        assert_eq!(spans[1].start_time, year("1871"));
+        assert_eq!(spans[1].end_time, None);
+
+        country.insert("capital", capital(&[])).unwrap();
+        country
+            .insert("capital", Field::Entity(EntityId::Wikidata(490)))
+            .unwrap();
+        assert_eq!(country.capital[&EntityId::Wikidata(220)].len(), 2);
+        assert_eq!(country.capital[&EntityId::Wikidata(490)], vec![]);
+        assert_eq!(
+            country.insert("capital", Field::Date(year("0330").unwrap())),
+            Err("expected a capital, got a date".to_string())
+        );
+    }
+}
@@ -0,0 +1,123 @@
+/// Splits a query into its PREFIX/BASE prologue and the SELECT part.
+fn split_prologue(sparql: &str) -> (&str, &str) {
+    match sparql.to_ascii_lowercase().find("select") {
+        Some(index) => (&sparql[..index], &sparql[index..]),
+        None => ("", sparql),
+    }
+}
+
+/// Variables listed between SELECT and WHERE, in projection order. An
+/// expression such as `(COUNT(?x) AS ?n)` projects only the variable after
+/// its `AS`, not the ones it reads.
+pub fn projected_vars(sparql: &str) -> Vec<String> {
+    let (_, select) = split_prologue(sparql);
+    let lower = select.to_ascii_lowercase();
+    let end = lower.find("where").unwrap_or(select.len());
+    let mut vars = vec![];
+    let mut depth = 0usize;
+    let mut after_as = false;
+    let projection = select[..end].replace('(', " ( ").replace(')', " ) ");
+    for token in projection.split_whitespace() {
+        match token {
+            "(" => depth += 1,
+            ")" => depth = depth.saturating_sub(1),
+            _ if token.eq_ignore_ascii_case("as") => after_as = depth > 0,
+            _ => {
+                if let Some(var) = token.strip_prefix('?').or_else(|| token.strip_prefix('$')) {
+                    if depth == 0 || after_as {
+                        vars.push(var.to_string());
+                    }
+                }
+                after_as = false;
+            }
+        }
+    }
+    vars
+}
+
+/// Wraps `sparql` in a subquery ordered by every projected variable so that
+/// LIMIT/OFFSET windows are stable between requests; `None` if it projects no
+/// variable to order by, like `SELECT *`.
+pub fn paged_query(sparql: &str, page_size: usize, offset: usize) -> Option<String> {
+    let (prologue, select) = split_prologue(sparql);
+    let vars = projected_vars(sparql);
+    if vars.is_empty() {
+        return None;
+    }
+    let order_by = vars
+        .iter()
+        .map(|var| format!("?{}", var))
+        .collect::<Vec<_>>()
+        .join(" ");
+    Some(format!(
+        "{}SELECT * WHERE {{
+{}
+}}
+ORDER BY {}
+LIMIT {} OFFSET {}",
+        prologue,
+        select.trim(),
+        order_by,
+        page_size,
+        offset
+    ))
+}
+
+/// A page is truncated when the server capped it below what we asked for,
+/// i.e. it returned exactly `max_rows` rows while we requested more.
+pub fn is_truncated(rows: usize, requested: Option<usize>, max_rows: Option<usize>) -> bool {
+    match (requested, max_rows) {
+        (Some(requested), Some(max_rows)) => max_rows < requested && rows >= max_rows,
+        (None, Some(max_rows)) => rows >= max_rows,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static QUERY: &str = "prefix dbo: <http://dbpedia.org/ontology/>
+select distinct ?battle ?abstract ?language where {
+    ?battle dbo:abstract ?abstract .
+}";
+
+    #[test]
+    fn test_projected_vars() {
+        assert_eq!(
+            projected_vars(QUERY),
+            vec!["battle", "abstract", "language"]
+        );
+    }
+
+    #[test]
+    fn test_projected_aggregates() {
+        let query = "select ?country (COUNT(?battle) AS ?battles) (SAMPLE(?label) as ?name) where {
+    ?battle ?p ?country .
+} group by ?country";
+        assert_eq!(projected_vars(query), vec!["country", "battles", "name"]);
+    }
+
+    #[test]
+    fn test_paged_query() {
+        let query = paged_query(QUERY, 100, 200).unwrap();
+        assert!(query.starts_with("prefix dbo: <http://dbpedia.org/ontology/>\nSELECT * WHERE {"));
+        assert!(query.ends_with("ORDER BY ?battle ?abstract ?language\nLIMIT 100 OFFSET 200"));
+    }
+
+    #[test]
+    fn test_paged_query_without_vars() {
+        let query = "select * where { ?battle dbo:abstract ?abstract . }";
+        assert_eq!(projected_vars(query), Vec::<String>::new());
+        assert_eq!(paged_query(query, 100, 0), None);
+    }
+
+    #[test]
+    fn test_is_truncated() {
+        assert!(is_truncated(10000, None, Some(10000)));
+        assert!(!is_truncated(9999, None, Some(10000)));
+        assert!(is_truncated(10000, Some(50000), Some(10000)));
+        assert!(!is_truncated(10000, Some(10000), Some(10000)));
+        assert!(!is_truncated(10000, None, None));
+    }
+}
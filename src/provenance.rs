@@ -0,0 +1,109 @@
+use crate::endpoint::{Dialect, Endpoint};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// One HTTP exchange that contributed rows to a result file.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RequestRecord {
+    pub http_status: u16,
+    /// Requests sent, including retries, before this one succeeded.
+    pub attempts: u32,
+    pub rows: usize,
+    /// OFFSET of the page when the result was paginated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+}
+
+/// How a raw result under `sparql/` was produced, written next to it as
+/// `{target}.meta.json` so that every dataset can be audited and reproduced.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub category: String,
+    pub target: String,
+    pub endpoint: String,
+    pub endpoint_uri: String,
+    pub dialect: Dialect,
+    /// The generated query, before any pagination wrapper.
+    pub query: String,
+    /// Seconds since the Unix epoch when the last page arrived.
+    pub fetched_at: u64,
+    pub rows: usize,
+    pub requests: Vec<RequestRecord>,
+    pub tool_version: String,
+}
+
+impl Provenance {
+    pub fn new(
+        category: &str,
+        target: &str,
+        endpoint: &Endpoint,
+        query: &str,
+        requests: Vec<RequestRecord>,
+    ) -> Self {
+        Provenance {
+            category: category.to_string(),
+            target: target.to_string(),
+            endpoint: endpoint.name.clone(),
+            endpoint_uri: endpoint.uri.clone(),
+            dialect: endpoint.dialect,
+            query: query.to_string(),
+            fetched_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            rows: requests.iter().map(|r| r.rows).sum(),
+            requests,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// `data/sparql/wikidata/country/label.json` -> `data/sparql/wikidata/country/label.meta.json`
+pub fn sidecar_path(result: &Path) -> PathBuf {
+    result.with_extension("meta.json")
+}
+
+pub fn is_sidecar(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|path| path.ends_with(".meta.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::EndpointRegistry;
+
+    #[test]
+    fn test_sidecar_path() {
+        let path = Path::new("data/sparql/wikidata/country/label.json");
+        let sidecar = sidecar_path(path);
+        assert_eq!(
+            sidecar,
+            Path::new("data/sparql/wikidata/country/label.meta.json")
+        );
+        assert!(is_sidecar(&sidecar));
+        assert!(!is_sidecar(path));
+    }
+
+    #[test]
+    fn test_rows_are_summed_over_pages() {
+        let registry = EndpointRegistry::builtin();
+        let endpoint = registry.get("dbpedia").unwrap();
+        let page = |offset, rows| RequestRecord {
+            http_status: 200,
+            attempts: 1,
+            rows,
+            offset: Some(offset),
+        };
+        let provenance = Provenance::new(
+            "battle",
+            "abstract",
+            endpoint,
+            "select ...",
+            vec![page(0, 10000), page(10000, 42)],
+        );
+        assert_eq!(provenance.rows, 10042);
+        assert_eq!(provenance.endpoint_uri, "https://dbpedia.org/sparql");
+    }
+}
@@ -0,0 +1,720 @@
+use crate::endpoint::Dialect;
+use crate::error::Error;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+use strum_macros::{Display, EnumString};
+
+/// Namespaces the generated queries may use. A query only declares the ones it
+/// uses, and rendering fails if one of them does not belong to the dialect.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Prefix {
+    Rdf,
+    Rdfs,
+    Owl,
+    Wd,
+    Wdt,
+    P,
+    Ps,
+    Pq,
+    Psv,
+    Pqv,
+    Pr,
+    Prov,
+    Wikibase,
+    Dbo,
+    Dbp,
+    Geo,
+}
+
+impl Prefix {
+    pub fn iri(self) -> &'static str {
+        match self {
+            Prefix::Rdf => "http://www.w3.org/1999/02/22-rdf-syntax-ns#",
+            Prefix::Rdfs => "http://www.w3.org/2000/01/rdf-schema#",
+            Prefix::Owl => "http://www.w3.org/2002/07/owl#",
+            Prefix::Wd => "http://www.wikidata.org/entity/",
+            Prefix::Wdt => "http://www.wikidata.org/prop/direct/",
+            Prefix::P => "http://www.wikidata.org/prop/",
+            Prefix::Ps => "http://www.wikidata.org/prop/statement/",
+            Prefix::Pq => "http://www.wikidata.org/prop/qualifier/",
+            Prefix::Psv => "http://www.wikidata.org/prop/statement/value/",
+            Prefix::Pqv => "http://www.wikidata.org/prop/qualifier/value/",
+            Prefix::Pr => "http://www.wikidata.org/prop/reference/",
+            Prefix::Prov => "http://www.w3.org/ns/prov#",
+            Prefix::Wikibase => "http://wikiba.se/ontology#",
+            Prefix::Dbo => "http://dbpedia.org/ontology/",
+            Prefix::Dbp => "http://dbpedia.org/property/",
+            Prefix::Geo => "http://www.w3.org/2003/01/geo/wgs84_pos#",
+        }
+    }
+
+    pub fn is_available(self, dialect: Dialect) -> bool {
+        match self {
+            Prefix::Rdf | Prefix::Rdfs | Prefix::Owl => true,
+            Prefix::Wd
+            | Prefix::Wdt
+            | Prefix::P
+            | Prefix::Ps
+            | Prefix::Pq
+            | Prefix::Psv
+            | Prefix::Pqv
+            | Prefix::Pr
+            | Prefix::Prov
+            | Prefix::Wikibase => dialect == Dialect::Wikidata,
+            Prefix::Dbo | Prefix::Dbp | Prefix::Geo => dialect == Dialect::Dbpedia,
+        }
+    }
+
+    pub fn term(self, local: &str) -> Term {
+        Term::Prefixed(self, local.to_string())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Term {
+    Var(String),
+    Iri(String),
+    Prefixed(Prefix, String),
+    Literal(String),
+}
+
+pub fn var(name: &str) -> Term {
+    Term::Var(name.to_string())
+}
+
+pub fn literal(value: &str) -> Term {
+    Term::Literal(value.to_string())
+}
+
+pub fn wd(id: &str) -> Term {
+    Prefix::Wd.term(id)
+}
+
+pub fn wdt(id: &str) -> Term {
+    Prefix::Wdt.term(id)
+}
+
+pub fn p(id: &str) -> Term {
+    Prefix::P.term(id)
+}
+
+pub fn ps(id: &str) -> Term {
+    Prefix::Ps.term(id)
+}
+
+pub fn pq(id: &str) -> Term {
+    Prefix::Pq.term(id)
+}
+
+pub fn dbo(name: &str) -> Term {
+    Prefix::Dbo.term(name)
+}
+
+/// A predicate, or a SPARQL 1.1 property path built from predicates.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Path {
+    Predicate(Term),
+    /// `a/b`
+    Sequence(Vec<Path>),
+    /// `a|b`
+    Alternative(Vec<Path>),
+    /// `a*`
+    ZeroOrMore(Box<Path>),
+    /// `^a`
+    Inverse(Box<Path>),
+}
+
+impl From<Term> for Path {
+    fn from(term: Term) -> Self {
+        Path::Predicate(term)
+    }
+}
+
+impl Path {
+    pub fn then(self, next: impl Into<Path>) -> Path {
+        match self {
+            Path::Sequence(mut paths) => {
+                paths.push(next.into());
+                Path::Sequence(paths)
+            }
+            path => Path::Sequence(vec![path, next.into()]),
+        }
+    }
+
+    pub fn or(self, other: impl Into<Path>) -> Path {
+        match self {
+            Path::Alternative(mut paths) => {
+                paths.push(other.into());
+                Path::Alternative(paths)
+            }
+            path => Path::Alternative(vec![path, other.into()]),
+        }
+    }
+
+    pub fn zero_or_more(self) -> Path {
+        Path::ZeroOrMore(Box::new(self))
+    }
+
+    pub fn inverse(self) -> Path {
+        Path::Inverse(Box::new(self))
+    }
+}
+
+impl FromStr for Term {
+    type Err = String;
+
+    /// `?var`, `<iri>` or a prefixed name such as `wd:Q6256`.
+    fn from_str(text: &str) -> Result<Self, String> {
+        if let Some(name) = text.strip_prefix('?') {
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Ok(var(name));
+            }
+        } else if let Some(iri) = text.strip_prefix('<') {
+            let iri = iri.strip_suffix('>').unwrap_or_default();
+            if !iri.is_empty() && !iri.contains(|c: char| c.is_whitespace() || c == '<' || c == '>')
+            {
+                return Ok(Term::Iri(iri.to_string()));
+            }
+        } else if let Some((prefix, local)) = text.split_once(':') {
+            let prefix =
+                Prefix::from_str(prefix).map_err(|_| format!("unknown prefix {}:", prefix))?;
+            if !local.is_empty() && !local.contains(|c: char| c.is_whitespace()) {
+                return Ok(prefix.term(local));
+            }
+        }
+        Err(format!("invalid term: {}", text))
+    }
+}
+
+impl FromStr for Path {
+    type Err = String;
+
+    /// A property path such as `wdt:P31/wdt:P279*` or `^wdt:P463|wdt:P150`.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut parser = PathParser {
+            text,
+            tokens: tokenize_path(text),
+            position: 0,
+        };
+        let path = parser.alternative()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(path),
+            Some(token) => Err(format!("unexpected {} in path {}", token, text)),
+        }
+    }
+}
+
+fn tokenize_path(text: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        // Operator characters inside an `<iri>` belong to it.
+        let token = &text[start.unwrap_or(i)..i];
+        let in_iri = token.starts_with('<') && !token.ends_with('>');
+        let is_operator = "()/|^*".contains(c) && !in_iri;
+        if is_operator || c.is_whitespace() {
+            if let Some(s) = start.take() {
+                tokens.push(&text[s..i]);
+            }
+            if is_operator {
+                tokens.push(&text[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push(&text[s..]);
+    }
+    tokens
+}
+
+struct PathParser<'a> {
+    text: &'a str,
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl PathParser<'_> {
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.tokens.get(self.position) == Some(&token);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn alternative(&mut self) -> Result<Path, String> {
+        let mut path = self.sequence()?;
+        while self.eat("|") {
+            path = path.or(self.sequence()?);
+        }
+        Ok(path)
+    }
+
+    fn sequence(&mut self) -> Result<Path, String> {
+        let mut path = self.unary()?;
+        while self.eat("/") {
+            path = path.then(self.unary()?);
+        }
+        Ok(path)
+    }
+
+    fn unary(&mut self) -> Result<Path, String> {
+        let inverse = self.eat("^");
+        let mut path = if self.eat("(") {
+            let path = self.alternative()?;
+            if !self.eat(")") {
+                return Err(format!("unbalanced parentheses in path {}", self.text));
+            }
+            path
+        } else {
+            let token = self
+                .tokens
+                .get(self.position)
+                .ok_or_else(|| format!("incomplete path {}", self.text))?;
+            self.position += 1;
+            match Term::from_str(token)? {
+                Term::Var(_) => return Err(format!("variable in path {}", self.text)),
+                term => Path::from(term),
+            }
+        };
+        if self.eat("*") {
+            path = path.zero_or_more();
+        }
+        Ok(if inverse { path.inverse() } else { path })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expr {
+    Term(Term),
+    Lang(Box<Expr>),
+    Str(Box<Expr>),
+    StrStarts(Box<Expr>, Box<Expr>),
+}
+
+impl From<Term> for Expr {
+    fn from(term: Term) -> Self {
+        Expr::Term(term)
+    }
+}
+
+pub fn lang(expr: impl Into<Expr>) -> Expr {
+    Expr::Lang(Box::new(expr.into()))
+}
+
+pub fn str(expr: impl Into<Expr>) -> Expr {
+    Expr::Str(Box::new(expr.into()))
+}
+
+pub fn strstarts(expr: impl Into<Expr>, start: impl Into<Expr>) -> Expr {
+    Expr::StrStarts(Box::new(expr.into()), Box::new(start.into()))
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Triple {
+        subject: Term,
+        path: Path,
+        object: Term,
+    },
+    /// `{ .. } UNION { .. } UNION ..`
+    Union(Vec<Vec<Pattern>>),
+    Optional(Vec<Pattern>),
+    Filter(Expr),
+    FilterNotExists(Vec<Pattern>),
+    Bind {
+        expr: Expr,
+        var: String,
+    },
+    Values {
+        var: String,
+        values: Vec<Term>,
+    },
+}
+
+pub fn triple(subject: Term, path: impl Into<Path>, object: Term) -> Pattern {
+    Pattern::Triple {
+        subject,
+        path: path.into(),
+        object,
+    }
+}
+
+pub fn bind(expr: impl Into<Expr>, var: &str) -> Pattern {
+    Pattern::Bind {
+        expr: expr.into(),
+        var: var.to_string(),
+    }
+}
+
+/// `?{subject} rdfs:label ?label` with the language tag of the label bound to `?language`.
+pub fn labelled(subject: &str) -> Vec<Pattern> {
+    vec![
+        triple(var(subject), Prefix::Rdfs.term("label"), var("label")),
+        bind(lang(var("label")), "language"),
+    ]
+}
+
+/// A SELECT query.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Query {
+    pub distinct: bool,
+    pub vars: Vec<String>,
+    pub patterns: Vec<Pattern>,
+}
+
+impl Query {
+    pub fn select<S: AsRef<str>>(vars: &[S]) -> Self {
+        Query {
+            distinct: false,
+            vars: vars.iter().map(|var| var.as_ref().to_string()).collect(),
+            patterns: vec![],
+        }
+    }
+
+    pub fn select_distinct<S: AsRef<str>>(vars: &[S]) -> Self {
+        Query {
+            distinct: true,
+            ..Query::select(vars)
+        }
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    pub fn patterns(mut self, patterns: impl IntoIterator<Item = Pattern>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// Every prefix used anywhere in the query, in declaration order.
+    pub fn prefixes(&self) -> BTreeSet<Prefix> {
+        let mut prefixes = BTreeSet::new();
+        for pattern in &self.patterns {
+            pattern_prefixes(pattern, &mut prefixes);
+        }
+        prefixes
+    }
+
+    /// The query text for an endpoint of `dialect`, with a PREFIX declaration
+    /// for every prefix it uses.
+    pub fn render(&self, dialect: Dialect) -> Result<String, Error> {
+        let prefixes = self.prefixes();
+        if let Some(prefix) = prefixes.iter().find(|p| !p.is_available(dialect)) {
+            return Err(Error::Config(format!(
+                "prefix {}: is not available on {} endpoints",
+                prefix, dialect
+            )));
+        }
+        let mut sparql = String::new();
+        for prefix in prefixes {
+            sparql.push_str(&format!("PREFIX {}: <{}>\n", prefix, prefix.iri()));
+        }
+        let vars: Vec<_> = self.vars.iter().map(|var| format!("?{}", var)).collect();
+        sparql.push_str(&format!(
+            "SELECT {}{} WHERE {{\n",
+            if self.distinct { "DISTINCT " } else { "" },
+            vars.join(" ")
+        ));
+        render_group(&self.patterns, 1, &mut sparql);
+        sparql.push('}');
+        Ok(sparql)
+    }
+}
+
+fn term_prefixes(term: &Term, prefixes: &mut BTreeSet<Prefix>) {
+    if let Term::Prefixed(prefix, _) = term {
+        prefixes.insert(*prefix);
+    }
+}
+
+fn path_prefixes(path: &Path, prefixes: &mut BTreeSet<Prefix>) {
+    match path {
+        Path::Predicate(term) => term_prefixes(term, prefixes),
+        Path::Sequence(paths) | Path::Alternative(paths) => {
+            paths.iter().for_each(|path| path_prefixes(path, prefixes))
+        }
+        Path::ZeroOrMore(path) | Path::Inverse(path) => path_prefixes(path, prefixes),
+    }
+}
+
+fn expr_prefixes(expr: &Expr, prefixes: &mut BTreeSet<Prefix>) {
+    match expr {
+        Expr::Term(term) => term_prefixes(term, prefixes),
+        Expr::Lang(expr) | Expr::Str(expr) => expr_prefixes(expr, prefixes),
+        Expr::StrStarts(a, b) => {
+            expr_prefixes(a, prefixes);
+            expr_prefixes(b, prefixes);
+        }
+    }
+}
+
+fn pattern_prefixes(pattern: &Pattern, prefixes: &mut BTreeSet<Prefix>) {
+    match pattern {
+        Pattern::Triple {
+            subject,
+            path,
+            object,
+        } => {
+            term_prefixes(subject, prefixes);
+            path_prefixes(path, prefixes);
+            term_prefixes(object, prefixes);
+        }
+        Pattern::Union(groups) => groups
+            .iter()
+            .flatten()
+            .for_each(|pattern| pattern_prefixes(pattern, prefixes)),
+        Pattern::Optional(group) | Pattern::FilterNotExists(group) => group
+            .iter()
+            .for_each(|pattern| pattern_prefixes(pattern, prefixes)),
+        Pattern::Filter(expr) | Pattern::Bind { expr, .. } => expr_prefixes(expr, prefixes),
+        Pattern::Values { values, .. } => {
+            values.iter().for_each(|term| term_prefixes(term, prefixes))
+        }
+    }
+}
+
+fn render_term(term: &Term) -> String {
+    match term {
+        Term::Var(name) => format!("?{}", name),
+        Term::Iri(iri) => format!("<{}>", iri),
+        Term::Prefixed(prefix, local) => format!("{}:{}", prefix, local),
+        Term::Literal(value) => format!(
+            "\"{}\"",
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        ),
+    }
+}
+
+/// Binding strength of a path operator, loosest first.
+fn precedence(path: &Path) -> u8 {
+    match path {
+        Path::Alternative(_) => 0,
+        Path::Sequence(_) => 1,
+        Path::ZeroOrMore(_) | Path::Inverse(_) => 2,
+        Path::Predicate(_) => 3,
+    }
+}
+
+fn render_path(path: &Path) -> String {
+    // An operand binding more loosely than its operator needs parentheses;
+    // operands of unary operators must be a single predicate to go without.
+    let operand = |path: &Path, operator: u8| {
+        if precedence(path) > operator && (operator < 2 || precedence(path) == 3) {
+            render_path(path)
+        } else {
+            format!("({})", render_path(path))
+        }
+    };
+    match path {
+        Path::Predicate(term) => render_term(term),
+        Path::Sequence(paths) => paths
+            .iter()
+            .map(|path| operand(path, 1))
+            .collect::<Vec<_>>()
+            .join("/"),
+        Path::Alternative(paths) => paths
+            .iter()
+            .map(|path| operand(path, 0))
+            .collect::<Vec<_>>()
+            .join("|"),
+        Path::ZeroOrMore(path) => format!("{}*", operand(path, 2)),
+        Path::Inverse(path) => format!("^{}", operand(path, 2)),
+    }
+}
+
+fn render_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Term(term) => render_term(term),
+        Expr::Lang(expr) => format!("LANG({})", render_expr(expr)),
+        Expr::Str(expr) => format!("STR({})", render_expr(expr)),
+        Expr::StrStarts(a, b) => format!("STRSTARTS({}, {})", render_expr(a), render_expr(b)),
+    }
+}
+
+fn render_group(patterns: &[Pattern], depth: usize, sparql: &mut String) {
+    let indent = "  ".repeat(depth);
+    for pattern in patterns {
+        match pattern {
+            Pattern::Triple {
+                subject,
+                path,
+                object,
+            } => sparql.push_str(&format!(
+                "{}{} {} {} .\n",
+                indent,
+                render_term(subject),
+                render_path(path),
+                render_term(object)
+            )),
+            Pattern::Union(groups) => {
+                for (i, group) in groups.iter().enumerate() {
+                    if i == 0 {
+                        sparql.push_str(&format!("{}{{\n", indent));
+                    } else {
+                        sparql.push_str(&format!("{}}} UNION {{\n", indent));
+                    }
+                    render_group(group, depth + 1, sparql);
+                }
+                sparql.push_str(&format!("{}}}\n", indent));
+            }
+            Pattern::Optional(group) => {
+                sparql.push_str(&format!("{}OPTIONAL {{\n", indent));
+                render_group(group, depth + 1, sparql);
+                sparql.push_str(&format!("{}}}\n", indent));
+            }
+            Pattern::Filter(expr) => {
+                sparql.push_str(&format!("{}FILTER ({})\n", indent, render_expr(expr)))
+            }
+            Pattern::FilterNotExists(group) => {
+                sparql.push_str(&format!("{}FILTER NOT EXISTS {{\n", indent));
+                render_group(group, depth + 1, sparql);
+                sparql.push_str(&format!("{}}}\n", indent));
+            }
+            Pattern::Bind { expr, var } => sparql.push_str(&format!(
+                "{}BIND ({} AS ?{})\n",
+                indent,
+                render_expr(expr),
+                var
+            )),
+            Pattern::Values { var, values } => {
+                let values: Vec<_> = values.iter().map(render_term).collect();
+                sparql.push_str(&format!(
+                    "{}VALUES ?{} {{ {} }}\n",
+                    indent,
+                    var,
+                    values.join(" ")
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_declares_used_prefixes() {
+        let query = Query::select_distinct(&["war", "label", "language"])
+            .pattern(triple(
+                var("war"),
+                Path::from(wdt("P31")).then(Path::from(wdt("P279")).zero_or_more()),
+                wd("Q198"),
+            ))
+            .patterns(labelled("war"));
+        assert_eq!(
+            query.render(Dialect::Wikidata).unwrap(),
+            "PREFIX rdfs: <http://www.w3.org/2000/01/rdf-schema#>
+PREFIX wd: <http://www.wikidata.org/entity/>
+PREFIX wdt: <http://www.wikidata.org/prop/direct/>
+SELECT DISTINCT ?war ?label ?language WHERE {
+  ?war wdt:P31/wdt:P279* wd:Q198 .
+  ?war rdfs:label ?label .
+  BIND (LANG(?label) AS ?language)
+}"
+        );
+    }
+
+    #[test]
+    fn test_render_groups() {
+        let query = Query::select(&["state"])
+            .pattern(Pattern::Union(vec![
+                vec![triple(var("state"), wdt("P31"), wd("Q7275"))],
+                vec![triple(var("state"), wdt("P31"), wd("Q133442"))],
+            ]))
+            .pattern(Pattern::FilterNotExists(vec![triple(
+                var("state"),
+                wdt("P31"),
+                wd("Q6256"),
+            )]))
+            .pattern(Pattern::Optional(vec![triple(
+                var("state"),
+                Path::from(wdt("P150")).inverse(),
+                var("league"),
+            )]))
+            .pattern(Pattern::Values {
+                var: "state".to_string(),
+                values: vec![wd("Q1"), wd("Q2")],
+            })
+            .pattern(Pattern::Filter(strstarts(
+                str(var("state")),
+                literal("http://"),
+            )));
+        let sparql = query.render(Dialect::Wikidata).unwrap();
+        assert!(sparql.contains(
+            "  {
+    ?state wdt:P31 wd:Q7275 .
+  } UNION {
+    ?state wdt:P31 wd:Q133442 .
+  }
+  FILTER NOT EXISTS {
+    ?state wdt:P31 wd:Q6256 .
+  }
+  OPTIONAL {
+    ?state ^wdt:P150 ?league .
+  }
+  VALUES ?state { wd:Q1 wd:Q2 }
+  FILTER (STRSTARTS(STR(?state), \"http://\"))
+"
+        ));
+        assert!(sparql.contains("SELECT ?state WHERE"));
+    }
+
+    #[test]
+    fn test_render_paths() {
+        let member = Path::from(wdt("P463")).inverse().or(wdt("P150"));
+        assert_eq!(render_path(&member), "^wdt:P463|wdt:P150");
+        let subclass = Path::from(wdt("P31"))
+            .then(wdt("P279"))
+            .zero_or_more()
+            .then(wd("Q1"));
+        assert_eq!(render_path(&subclass), "(wdt:P31/wdt:P279)*/wd:Q1");
+    }
+
+    #[test]
+    fn test_render_rejects_prefixes_of_other_dialects() {
+        let query =
+            Query::select(&["country"]).pattern(triple(var("country"), wdt("P31"), wd("Q6256")));
+        assert!(matches!(
+            query.render(Dialect::Dbpedia),
+            Err(Error::Config(_))
+        ));
+        assert!(query.render(Dialect::Wikidata).is_ok());
+    }
+
+    #[test]
+    fn test_parse_path() {
+        for text in [
+            "wdt:P31",
+            "wdt:P31/wdt:P279*",
+            "^wdt:P463|wdt:P150",
+            "(wdt:P31/wdt:P279)*/wd:Q1",
+            "<http://schema.org/about>/rdfs:label",
+        ] {
+            let path = Path::from_str(text).unwrap();
+            assert_eq!(render_path(&path), text);
+        }
+        assert_eq!(
+            Path::from_str("wdt:P31 / wdt:P279 *").unwrap(),
+            Path::from(wdt("P31")).then(Path::from(wdt("P279")).zero_or_more())
+        );
+        assert!(Path::from_str("schema:about").is_err());
+        assert!(Path::from_str("(wdt:P31").is_err());
+        assert!(Path::from_str("wdt:P31/").is_err());
+        assert!(Path::from_str("?p").is_err());
+    }
+
+    #[test]
+    fn test_literal_is_escaped() {
+        assert_eq!(render_term(&literal("a \"b\"\\")), "\"a \\\"b\\\"\\\\\"");
+    }
+}
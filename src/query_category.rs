@@ -0,0 +1,612 @@
+use crate::dbpedia_queries;
+use crate::definition;
+use crate::endpoint::Dialect;
+use crate::error::Error;
+use crate::merge::{merger, Merger};
+use crate::model::Record;
+use crate::query_builder::Query;
+use crate::wikidata_queries;
+use crate::wikidata_queries::sparql_types::{Object, TermType};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::path::Path;
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+/// A family of queries about one kind of entity, e.g. countries or battles.
+/// Adding a category means implementing this trait and listing it in the
+/// `categories()` of its dialect.
+pub trait QueryCategory: Sync {
+    /// Name on the command line and directory name under `sparql/{endpoint}/`.
+    fn name(&self) -> &str;
+
+    /// Variable that binds the entity in every query of the category.
+    fn subject(&self) -> &str {
+        self.name()
+    }
+
+    /// Every target that can be fetched, in the order bulk runs fetch them.
+    fn targets(&self) -> Vec<String>;
+
+    /// The query of `target`, or `None` if the category has no such target.
+    fn gen_query(&self, target: &str) -> Option<Query>;
+
+    /// Variables the results of `target` are expected to bind.
+    fn output_vars(&self, target: &str) -> Option<Vec<String>> {
+        self.gen_query(target).map(|query| query.vars)
+    }
+
+    /// How merge reads the bindings of `target`, or `None` if the category has
+    /// no such target.
+    fn statement(&self, target: &str) -> Option<Statement>;
+
+    /// How merge writes the category: into its own model, or into `Record`s
+    /// that keep every field as it is read.
+    fn merger(&self) -> Merger {
+        merger::<Record>()
+    }
+}
+
+/// The shape of the bindings of one target: `?{value}` is the value of the
+/// field named after it, and the other variables describe it, e.g. the start
+/// and end time qualifiers of a capital statement or the language of a label.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub value: ValueVar,
+    /// Variable bound to the language tag of a text, e.g. `?language`.
+    pub language: Option<String>,
+    /// Variable bound to the `wikibase:rank` of the statement.
+    pub rank: Option<String>,
+    pub qualifiers: Vec<ValueVar>,
+    /// Variables bound to the parts of its references, when they are fetched.
+    pub references: Option<ReferenceVars>,
+}
+
+/// A variable bound to a value, with the variables bound to the precision and
+/// calendar model of a Wikidata time value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValueVar {
+    pub name: String,
+    pub time: Option<TimeVars>,
+    /// Datatype IRI of its literals, for results that drop datatypes, like CSV.
+    pub datatype: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct TimeVars {
+    pub precision: String,
+    pub calendar: String,
+}
+
+/// Variables bound to the stated in (P248), reference URL (P854) and retrieved
+/// (P813) of a reference.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReferenceVars {
+    pub stated_in: String,
+    pub reference_url: String,
+    pub retrieved: String,
+}
+
+impl Statement {
+    pub fn new(value: ValueVar) -> Self {
+        Statement {
+            value,
+            language: None,
+            rank: None,
+            qualifiers: vec![],
+            references: None,
+        }
+    }
+
+    /// A language-tagged text bound to `?{value}`, with its tag bound to `?{language}`.
+    pub fn text(value: &str, language: &str) -> Self {
+        Statement {
+            language: Some(language.to_string()),
+            ..Self::new(ValueVar::plain(value))
+        }
+    }
+
+    pub fn rank(mut self, rank: String) -> Self {
+        self.rank = Some(rank);
+        self
+    }
+
+    pub fn qualifier(mut self, qualifier: ValueVar) -> Self {
+        self.qualifiers.push(qualifier);
+        self
+    }
+
+    pub fn references(mut self, references: ReferenceVars) -> Self {
+        self.references = Some(references);
+        self
+    }
+
+    /// Declares the datatype of its values; see `ValueVar::typed`.
+    pub fn typed(mut self, datatype: &str) -> Self {
+        self.value = self.value.typed(datatype);
+        self
+    }
+
+    /// Whether its values are dated by time qualifiers, like the capitals of a
+    /// country over time.
+    pub fn is_dated(&self) -> bool {
+        self.qualifiers
+            .iter()
+            .any(|qualifier| qualifier.time.is_some())
+    }
+
+    /// Every variable the statement reads.
+    pub fn vars(&self) -> Vec<String> {
+        let mut vars = self.value.vars();
+        vars.extend(self.language.clone());
+        vars.extend(self.rank.clone());
+        for qualifier in &self.qualifiers {
+            vars.extend(qualifier.vars());
+        }
+        vars.extend(self.references.iter().flat_map(ReferenceVars::vars));
+        vars
+    }
+}
+
+impl ValueVar {
+    pub fn plain(name: &str) -> Self {
+        ValueVar {
+            name: name.to_string(),
+            time: None,
+            datatype: None,
+        }
+    }
+
+    /// Declares the datatype of its literals, so that they are read the same
+    /// whether or not the results keep it.
+    pub fn typed(mut self, datatype: &str) -> Self {
+        self.datatype = Some(datatype.to_string());
+        self
+    }
+
+    /// `object`, bound to the variable, with the declared datatype if it is a
+    /// literal that came without one or a language tag.
+    pub fn with_datatype<'a>(&self, object: &'a Object) -> Cow<'a, Object> {
+        match &self.datatype {
+            Some(datatype)
+                if object.r#type == TermType::Literal
+                    && object.datatype.is_none()
+                    && object.lang.is_none() =>
+            {
+                Cow::Owned(Object {
+                    datatype: Some(datatype.clone()),
+                    ..object.clone()
+                })
+            }
+            _ => Cow::Borrowed(object),
+        }
+    }
+
+    /// The variable and those of its precision and calendar model, in the
+    /// order queries project them.
+    pub fn vars(&self) -> Vec<String> {
+        let mut vars = vec![self.name.clone()];
+        if let Some(time) = &self.time {
+            vars.push(time.precision.clone());
+            vars.push(time.calendar.clone());
+        }
+        vars
+    }
+}
+
+impl ReferenceVars {
+    pub fn vars(&self) -> Vec<String> {
+        vec![
+            self.stated_in.clone(),
+            self.reference_url.clone(),
+            self.retrieved.clone(),
+        ]
+    }
+}
+
+/// Targets of a category backed by a strum enum whose `Default` variant is the
+/// `Unknown` placeholder.
+pub fn enum_targets<T>() -> Vec<String>
+where
+    T: IntoEnumIterator + Display + PartialEq + Default,
+{
+    T::iter()
+        .filter(|target| *target != T::default())
+        .map(|target| target.to_string())
+        .collect()
+}
+
+pub fn parse_target<T>(target: &str) -> Option<T>
+where
+    T: FromStr + PartialEq + Default,
+{
+    T::from_str(target)
+        .ok()
+        .filter(|target| *target != T::default())
+}
+
+/// The categories known for one dialect.
+pub struct Categories {
+    dialect: Dialect,
+    categories: Vec<Box<dyn QueryCategory>>,
+}
+
+impl Categories {
+    pub fn builtin(dialect: Dialect) -> Self {
+        let categories = match dialect {
+            Dialect::Wikidata => wikidata_queries::get_query::categories(),
+            Dialect::Dbpedia => dbpedia_queries::get_query::categories(),
+        };
+        Categories {
+            dialect,
+            categories,
+        }
+    }
+
+    /// Built-in categories plus the definitions for `dialect` in `definitions`.
+    pub fn load(dialect: Dialect, definitions: Option<&Path>) -> Result<Self, Error> {
+        let mut categories = Self::builtin(dialect);
+        if let Some(directory) = definitions {
+            for (definition_dialect, category) in definition::load(directory)? {
+                if definition_dialect == dialect {
+                    categories.insert(Box::new(category))?;
+                }
+            }
+        }
+        Ok(categories)
+    }
+
+    /// Adds `category`; a category with the name of a known one contributes its
+    /// targets to it, replacing the targets it redefines.
+    pub fn insert(&mut self, category: Box<dyn QueryCategory>) -> Result<(), Error> {
+        let Some(index) = self
+            .categories
+            .iter()
+            .position(|c| c.name() == category.name())
+        else {
+            self.categories.push(category);
+            return Ok(());
+        };
+        if self.categories[index].subject() != category.subject() {
+            return Err(Error::Config(format!(
+                "{} binds its entity to ?{}, not ?{}",
+                category.name(),
+                self.categories[index].subject(),
+                category.subject()
+            )));
+        }
+        let base = self.categories.remove(index);
+        self.categories.insert(
+            index,
+            Box::new(Layered {
+                base,
+                extra: category,
+            }),
+        );
+        Ok(())
+    }
+
+    /// Every category fetching the references of its statements as well.
+    pub fn with_references(self) -> Self {
+        let categories = self
+            .categories
+            .into_iter()
+            .map(|base| Box::new(Referenced { base }) as Box<dyn QueryCategory>)
+            .collect();
+        Categories {
+            dialect: self.dialect,
+            categories,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &dyn QueryCategory> {
+        self.categories.iter().map(|category| category.as_ref())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn QueryCategory, Error> {
+        self.iter()
+            .find(|category| category.name() == name)
+            .ok_or_else(|| Error::UnknownCategory(name.to_string()))
+    }
+
+    /// The query text of `target` for an endpoint of this dialect.
+    pub fn gen_query(&self, category: &str, target: &str) -> Result<String, Error> {
+        self.get(category)?
+            .gen_query(target)
+            .ok_or_else(|| Error::UnknownTarget {
+                category: category.to_string(),
+                target: target.to_string(),
+            })?
+            .render(self.dialect)
+    }
+}
+
+/// A category extended by another one with the same name and subject.
+struct Layered {
+    base: Box<dyn QueryCategory>,
+    extra: Box<dyn QueryCategory>,
+}
+
+impl QueryCategory for Layered {
+    fn name(&self) -> &str {
+        self.base.name()
+    }
+
+    fn subject(&self) -> &str {
+        self.base.subject()
+    }
+
+    fn targets(&self) -> Vec<String> {
+        let mut targets = self.base.targets();
+        for target in self.extra.targets() {
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        targets
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        self.extra
+            .gen_query(target)
+            .or_else(|| self.base.gen_query(target))
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        if self.extra.gen_query(target).is_some() {
+            self.extra.statement(target)
+        } else {
+            self.base.statement(target)
+        }
+    }
+
+    fn merger(&self) -> Merger {
+        self.base.merger()
+    }
+}
+
+/// A category whose queries fetch the references of their statements.
+struct Referenced {
+    base: Box<dyn QueryCategory>,
+}
+
+impl QueryCategory for Referenced {
+    fn name(&self) -> &str {
+        self.base.name()
+    }
+
+    fn subject(&self) -> &str {
+        self.base.subject()
+    }
+
+    fn targets(&self) -> Vec<String> {
+        self.base.targets()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        let query = self.base.gen_query(target)?;
+        Some(wikidata_queries::with_references(query, target))
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        let statement = self.base.statement(target)?;
+        let query = self.base.gen_query(target)?;
+        Some(if wikidata_queries::reads_statement(&query, target) {
+            statement.references(wikidata_queries::reference_vars(target))
+        } else {
+            statement
+        })
+    }
+
+    fn merger(&self) -> Merger {
+        self.base.merger()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wikidata_queries::{ranked, ranked_time, reference_vars, time_var};
+    use oxigraph::model::vocab::geosparql;
+
+    #[test]
+    fn test_gen_query() {
+        let categories = Categories::builtin(Dialect::Wikidata);
+
+        let query = categories.gen_query("country", "inception");
+        assert!(query.unwrap().contains("?country ?inception"));
+
+        let query = categories.gen_query("country", "dissolution");
+        assert!(query.unwrap().contains("?country ?dissolution"));
+
+        let query = categories.gen_query("hoge", "hoge");
+        assert!(matches!(query, Err(Error::UnknownCategory(_))));
+
+        let query = categories.gen_query("country", "hoge");
+        assert!(matches!(query, Err(Error::UnknownTarget { .. })));
+
+        let query = categories.gen_query("country", "Unknown");
+        assert!(matches!(query, Err(Error::UnknownTarget { .. })));
+
+        let query = categories.gen_query("state", "hoge");
+        assert!(matches!(query, Err(Error::UnknownTarget { .. })));
+
+        let query = categories.gen_query("league", "hoge");
+        assert!(matches!(query, Err(Error::UnknownTarget { .. })));
+    }
+
+    #[test]
+    fn test_dbpedia_gen_query() {
+        let categories = Categories::builtin(Dialect::Dbpedia);
+
+        let query = categories.gen_query("battle", "abstract");
+        assert!(query.unwrap().contains("?battle ?abstract ?language"));
+
+        let query = categories.gen_query("hoge", "hoge");
+        assert!(matches!(query, Err(Error::UnknownCategory(_))));
+
+        let query = categories.gen_query("battle", "hoge");
+        assert!(matches!(query, Err(Error::UnknownTarget { .. })));
+    }
+
+    #[test]
+    fn test_every_target_generates_a_query() {
+        for dialect in [Dialect::Wikidata, Dialect::Dbpedia] {
+            let categories = Categories::builtin(dialect);
+            for category in categories.iter() {
+                for target in category.targets() {
+                    let query = categories.gen_query(category.name(), &target);
+                    assert!(query.is_ok(), "{} {}", category.name(), target);
+                    let vars = category.output_vars(&target).unwrap();
+                    assert_eq!(
+                        vars.first().map(String::as_str),
+                        Some(category.subject()),
+                        "{} {}",
+                        category.name(),
+                        target
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_target_declares_its_statement() {
+        for dialect in [Dialect::Wikidata, Dialect::Dbpedia] {
+            for categories in [
+                Categories::builtin(dialect),
+                Categories::builtin(dialect).with_references(),
+            ] {
+                for category in categories.iter() {
+                    for target in category.targets() {
+                        let statement = category.statement(&target);
+                        let statement = statement.unwrap_or_else(|| {
+                            panic!("{} {}: no statement", category.name(), target)
+                        });
+                        let vars = category.output_vars(&target).unwrap();
+                        for var in statement.vars() {
+                            assert!(
+                                vars.contains(&var),
+                                "{} {}: {} is not projected",
+                                category.name(),
+                                target,
+                                var
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_statements() {
+        let categories = Categories::builtin(Dialect::Wikidata);
+        let country = categories.get("country").unwrap();
+        assert_eq!(
+            country.statement("capital"),
+            Some(
+                ranked("capital")
+                    .qualifier(time_var("startTime"))
+                    .qualifier(time_var("endTime"))
+                    .qualifier(time_var("pointInTime"))
+            )
+        );
+        assert_eq!(
+            country.statement("label"),
+            Some(Statement::text("label", "language"))
+        );
+        assert_eq!(
+            country.statement("inception"),
+            Some(ranked_time("inception"))
+        );
+        assert_eq!(country.statement("hoge"), None);
+
+        let categories = Categories::builtin(Dialect::Dbpedia);
+        let capital = categories.get("capital").unwrap();
+        assert_eq!(
+            capital.statement("coordinates"),
+            Some(
+                Statement::new(ValueVar::plain("coordinates"))
+                    .typed(geosparql::WKT_LITERAL.as_str())
+            )
+        );
+    }
+
+    #[test]
+    fn test_with_references() {
+        let categories = Categories::builtin(Dialect::Wikidata).with_references();
+        let country = categories.get("country").unwrap();
+        let query = categories.gen_query("country", "inception").unwrap();
+        assert!(query.contains("?inception_statement prov:wasDerivedFrom ?inception_reference ."));
+        assert!(query.contains("?inception_reference pr:P248 ?inceptionStatedIn ."));
+        let vars = country.output_vars("inception").unwrap();
+        assert!(vars.ends_with(&[
+            "inceptionStatedIn".to_string(),
+            "inceptionReferenceUrl".to_string(),
+            "inceptionRetrieved".to_string()
+        ]));
+        assert_eq!(
+            country.statement("inception"),
+            Some(ranked_time("inception").references(reference_vars("inception")))
+        );
+
+        let query = categories.gen_query("country", "label").unwrap();
+        assert!(!query.contains("prov:wasDerivedFrom"));
+        assert_eq!(
+            country.statement("label"),
+            Some(Statement::text("label", "language"))
+        );
+    }
+
+    #[test]
+    fn test_insert_extends_a_known_category() {
+        let definition: definition::Definition = toml::from_str(
+            r#"
+name = "country"
+dialect = "wikidata"
+classes = ["wd:Q6256"]
+
+[[targets]]
+name = "population"
+property = "P1082"
+
+[[targets]]
+name = "flag"
+path = "wdt:P163"
+"#,
+        )
+        .unwrap();
+        let mut categories = Categories::builtin(Dialect::Wikidata);
+        let builtin = categories.get("country").unwrap().targets();
+        categories
+            .insert(Box::new(definition.compile().unwrap()))
+            .unwrap();
+
+        let country = categories.get("country").unwrap();
+        assert_eq!(country.targets().len(), builtin.len() + 1);
+        assert!(categories
+            .gen_query("country", "population")
+            .unwrap()
+            .contains("?population_statement ps:P1082 ?population ."));
+        assert!(categories
+            .gen_query("country", "flag")
+            .unwrap()
+            .contains("wdt:P163"));
+        assert!(categories
+            .gen_query("country", "inception")
+            .unwrap()
+            .contains("psv:P571"));
+
+        assert_eq!(country.statement("population"), Some(ranked("population")));
+        assert_eq!(
+            country.statement("inception"),
+            Some(ranked_time("inception"))
+        );
+
+        let mut definition = definition;
+        definition.subject = Some("state".to_string());
+        let result = categories.insert(Box::new(definition.compile().unwrap()));
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::endpoint::RateLimit;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A token bucket shared by every request sent to one endpoint.
+/// A token is added every `interval` up to `capacity`; each request takes one.
+pub struct TokenBucket {
+    capacity: f64,
+    interval: Duration,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    pub fn new(rate_limit: &RateLimit) -> Self {
+        let capacity = rate_limit.burst.max(1) as f64;
+        TokenBucket {
+            capacity,
+            interval: Duration::from_millis(rate_limit.interval_ms),
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    /// Takes a token if one is available at `now`, otherwise returns how long to wait for one.
+    fn try_acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(paused_until) = state.paused_until {
+            if paused_until > now {
+                return Err(paused_until - now);
+            }
+            state.paused_until = None;
+        }
+        if !self.interval.is_zero() {
+            let elapsed = now.saturating_duration_since(state.updated);
+            let refill = elapsed.as_secs_f64() / self.interval.as_secs_f64();
+            state.tokens = (state.tokens + refill).min(self.capacity);
+        } else {
+            state.tokens = self.capacity;
+        }
+        state.updated = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.interval.mul_f64(1.0 - state.tokens))
+        }
+    }
+
+    /// Holds back every caller for `wait`, e.g. when the server sent `Retry-After`.
+    pub fn pause(&self, wait: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let until = Instant::now() + wait;
+        if state
+            .paused_until
+            .is_none_or(|paused_until| paused_until < until)
+        {
+            state.paused_until = Some(until);
+        }
+    }
+
+    /// Blocks until a token is available.
+    pub fn acquire(&self) {
+        while let Err(wait) = self.try_acquire(Instant::now()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::new(&RateLimit {
+            interval_ms: 1000,
+            burst: 2,
+        });
+        let start = Instant::now();
+        assert!(bucket.try_acquire(start).is_ok());
+        assert!(bucket.try_acquire(start).is_ok());
+        assert_eq!(bucket.try_acquire(start), Err(Duration::from_millis(1000)));
+
+        let later = start + Duration::from_millis(250);
+        assert_eq!(bucket.try_acquire(later), Err(Duration::from_millis(750)));
+
+        let later = start + Duration::from_millis(1000);
+        assert!(bucket.try_acquire(later).is_ok());
+        assert!(bucket.try_acquire(later).is_err());
+
+        let much_later = start + Duration::from_secs(60);
+        assert!(bucket.try_acquire(much_later).is_ok());
+        assert!(bucket.try_acquire(much_later).is_ok());
+        assert!(bucket.try_acquire(much_later).is_err());
+    }
+
+    #[test]
+    fn test_pause() {
+        let bucket = TokenBucket::new(&RateLimit {
+            interval_ms: 0,
+            burst: 1,
+        });
+        let now = Instant::now();
+        assert!(bucket.try_acquire(now).is_ok());
+        bucket.pause(Duration::from_secs(30));
+        let wait = bucket
+            .try_acquire(now + Duration::from_secs(10))
+            .unwrap_err();
+        assert!(wait >= Duration::from_secs(20) && wait < Duration::from_secs(21));
+        assert!(bucket.try_acquire(now + Duration::from_secs(31)).is_ok());
+    }
+}
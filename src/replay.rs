@@ -0,0 +1,101 @@
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureMode {
+    /// Send every request and write its response to a fixture.
+    Record,
+    /// Answer every request from its fixture without touching the network.
+    Replay,
+}
+
+/// Where the responses of an endpoint are recorded to or replayed from.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Fixtures {
+    pub dir: String,
+    pub mode: FixtureMode,
+}
+
+/// One recorded HTTP response, stored as `{dir}/{fingerprint of the query}.json`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    /// The query that was sent, kept to tell fixtures apart when reading them.
+    pub query: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after: Option<String>,
+    pub body: String,
+}
+
+impl Fixtures {
+    pub fn path(&self, query: &str) -> PathBuf {
+        PathBuf::from(format!("{}/{:016x}.json", self.dir, fingerprint(query)))
+    }
+
+    pub fn load(&self, query: &str) -> Result<Fixture, Error> {
+        let path = self.path(query);
+        let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+        let fixture: Fixture = serde_json::from_str(&text).map_err(|e| Error::Decode {
+            source: path.display().to_string(),
+            message: e.to_string(),
+        })?;
+        if fixture.query != query {
+            return Err(Error::Config(format!(
+                "{}: fixture was recorded for another query",
+                path.display()
+            )));
+        }
+        Ok(fixture)
+    }
+
+    pub fn save(&self, fixture: &Fixture) -> Result<(), Error> {
+        let path = self.path(&fixture.query);
+        fs::create_dir_all(&self.dir).map_err(|e| Error::io(&path, e))?;
+        let json = serde_json::to_string_pretty(fixture).unwrap();
+        fs::write(&path, json).map_err(|e| Error::io(&path, e))
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` stays the same across Rust releases
+/// so that fixtures keep their names.
+fn fingerprint(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_is_stable() {
+        assert_eq!(fingerprint(""), 0xcbf29ce484222325);
+        assert_eq!(fingerprint("a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("tevere_data-replay-{}", std::process::id()));
+        let fixtures = Fixtures {
+            dir: dir.display().to_string(),
+            mode: FixtureMode::Replay,
+        };
+        let fixture = Fixture {
+            query: "SELECT ?s WHERE { ?s ?p ?o }".to_string(),
+            status: 429,
+            retry_after: Some("5".to_string()),
+            body: String::new(),
+        };
+        fixtures.save(&fixture).unwrap();
+        assert_eq!(fixtures.load(&fixture.query).unwrap(), fixture);
+        assert!(matches!(
+            fixtures.load("SELECT ?o WHERE { ?s ?p ?o }"),
+            Err(Error::Io { .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,522 @@
+use crate::wikidata_queries::sparql_types::{Head, Object, TermType};
+use oxigraph::sparql::results::{
+    QueryResultsFormat, QueryResultsParseError, QueryResultsParser, ReaderQueryResultsParserOutput,
+};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead};
+
+/// The encoding an endpoint is asked to send its results in. Every format is
+/// read into the same bindings.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultFormat {
+    #[default]
+    Json,
+    Xml,
+    /// Plain values without types or language tags: IRIs are told from
+    /// literals by their `http(s)://` scheme, and merge gives literals the
+    /// datatype their statement declares (see `ValueVar::typed`). Language tags
+    /// are read from the variables queries bind them to.
+    Csv,
+    /// Terms in N-Triples syntax, as typed as JSON.
+    Tsv,
+}
+
+impl ResultFormat {
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ResultFormat::Json => "application/sparql-results+json",
+            ResultFormat::Xml => "application/sparql-results+xml",
+            ResultFormat::Csv => "text/csv",
+            ResultFormat::Tsv => "text/tab-separated-values",
+        }
+    }
+
+    /// Reads the results in `reader`, handing every binding to `handler` as
+    /// soon as it is read so that a result of any size is never held in
+    /// memory as a whole.
+    pub fn read<R: BufRead, H: ResultHandler>(
+        self,
+        reader: R,
+        handler: &mut H,
+    ) -> Result<(), ReadError> {
+        match self {
+            ResultFormat::Json => read_json(reader, handler),
+            ResultFormat::Xml => read_terms(QueryResultsFormat::Xml, reader, handler),
+            ResultFormat::Csv => read_csv(reader, handler),
+            ResultFormat::Tsv => read_terms(QueryResultsFormat::Tsv, reader, handler),
+        }
+    }
+}
+
+/// Takes the parts of one response as they are read.
+pub trait ResultHandler {
+    /// The head, before the bindings unless JSON results put it after them.
+    fn head(&mut self, _head: Head) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn binding(&mut self, binding: HashMap<String, Object>) -> io::Result<()>;
+
+    /// The result of an ASK query.
+    fn boolean(&mut self, _boolean: bool) {}
+}
+
+impl<F: FnMut(HashMap<String, Object>) -> io::Result<()>> ResultHandler for F {
+    fn binding(&mut self, binding: HashMap<String, Object>) -> io::Result<()> {
+        self(binding)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    /// The body could not be read or ended early, e.g. because the connection
+    /// dropped.
+    Io(io::Error),
+    Invalid(String),
+    /// The handler failed to take a binding.
+    Handler(io::Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Io(e) | ReadError::Handler(e) => write!(f, "{}", e),
+            ReadError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<QueryResultsParseError> for ReadError {
+    fn from(e: QueryResultsParseError) -> Self {
+        match e {
+            QueryResultsParseError::Io(e) => ReadError::Io(e),
+            e => ReadError::Invalid(e.to_string()),
+        }
+    }
+}
+
+/// A body that ends before its document does was cut off in transit, like
+/// one that fails to read; only a document that is complete but wrong is
+/// `Invalid`.
+impl From<serde_json::Error> for ReadError {
+    fn from(e: serde_json::Error) -> Self {
+        match e.classify() {
+            Category::Io | Category::Eof => ReadError::Io(e.into()),
+            _ => ReadError::Invalid(e.to_string()),
+        }
+    }
+}
+
+/// Reads a format that keeps every term as RDF.
+fn read_terms<R: BufRead, H: ResultHandler>(
+    format: QueryResultsFormat,
+    reader: R,
+    handler: &mut H,
+) -> Result<(), ReadError> {
+    let parser = QueryResultsParser::from_format(format);
+    match parser.for_reader(reader)? {
+        ReaderQueryResultsParserOutput::Solutions(solutions) => {
+            let vars = solutions
+                .variables()
+                .iter()
+                .map(|var| var.as_str().to_string())
+                .collect();
+            let head = Head { vars, link: vec![] };
+            handler.head(head).map_err(ReadError::Handler)?;
+            for solution in solutions {
+                let binding = solution?
+                    .iter()
+                    .map(|(var, term)| (var.as_str().to_string(), Object::from(term)))
+                    .collect();
+                handler.binding(binding).map_err(ReadError::Handler)?;
+            }
+        }
+        ReaderQueryResultsParserOutput::Boolean(boolean) => handler.boolean(boolean),
+    }
+    Ok(())
+}
+
+fn read_csv<R: BufRead, H: ResultHandler>(reader: R, handler: &mut H) -> Result<(), ReadError> {
+    let mut records = CsvRecords {
+        reader,
+        line: String::new(),
+    };
+    let vars = records
+        .next_record()?
+        .ok_or_else(|| ReadError::Invalid("missing header row".to_string()))?;
+    let head = Head {
+        vars: vars.clone(),
+        link: vec![],
+    };
+    handler.head(head).map_err(ReadError::Handler)?;
+    let mut row = 0;
+    while let Some(record) = records.next_record()? {
+        row += 1;
+        if record.len() != vars.len() {
+            return Err(ReadError::Invalid(format!(
+                "row {} has {} values for {} variables",
+                row,
+                record.len(),
+                vars.len()
+            )));
+        }
+        let binding = vars
+            .iter()
+            .zip(record)
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(var, value)| (var.clone(), csv_object(value)))
+            .collect();
+        handler.binding(binding).map_err(ReadError::Handler)?;
+    }
+    Ok(())
+}
+
+/// Reads SPARQL JSON results with `serde_json`, handing over every binding of
+/// `results.bindings` as the array is read; the head and boolean may come
+/// before or after it.
+fn read_json<R: BufRead, H: ResultHandler>(reader: R, handler: &mut H) -> Result<(), ReadError> {
+    let mut failed = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let document = JsonMember {
+        handler,
+        failed: &mut failed,
+        member: Member::Document,
+    };
+    let read = document
+        .deserialize(&mut deserializer)
+        .and_then(|()| deserializer.end());
+    match (read, failed) {
+        (_, Some(e)) => Err(ReadError::Handler(e)),
+        (read, None) => Ok(read?),
+    }
+}
+
+/// The object or array of SPARQL JSON results a `JsonMember` reads.
+#[derive(Clone, Copy)]
+enum Member {
+    Document,
+    Results,
+    Bindings,
+}
+
+/// Reads one member of SPARQL JSON results into `handler`. A handler error is
+/// kept in `failed`, since serde only carries its own errors.
+struct JsonMember<'a, H> {
+    handler: &'a mut H,
+    failed: &'a mut Option<io::Error>,
+    member: Member,
+}
+
+impl<H: ResultHandler> JsonMember<'_, H> {
+    fn handled<E: de::Error>(&mut self, result: io::Result<()>) -> Result<(), E> {
+        result.map_err(|e| {
+            let error = E::custom(&e);
+            *self.failed = Some(e);
+            error
+        })
+    }
+
+    fn nested(&mut self, member: Member) -> JsonMember<'_, H> {
+        JsonMember {
+            handler: self.handler,
+            failed: self.failed,
+            member,
+        }
+    }
+}
+
+impl<'de, H: ResultHandler> DeserializeSeed<'de> for JsonMember<'_, H> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        match self.member {
+            Member::Bindings => deserializer.deserialize_seq(self),
+            Member::Document | Member::Results => deserializer.deserialize_map(self),
+        }
+    }
+}
+
+impl<'de, H: ResultHandler> Visitor<'de> for JsonMember<'_, H> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.member {
+            Member::Document => write!(f, "SPARQL JSON results"),
+            Member::Results => write!(f, "an object with the bindings"),
+            Member::Bindings => write!(f, "an array of bindings"),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match (self.member, key.as_str()) {
+                (Member::Document, "head") => {
+                    let head = map.next_value()?;
+                    let handled = self.handler.head(head);
+                    self.handled(handled)?;
+                }
+                (Member::Document, "boolean") => self.handler.boolean(map.next_value()?),
+                (Member::Document, "results") => {
+                    map.next_value_seed(self.nested(Member::Results))?
+                }
+                (Member::Results, "bindings") => {
+                    map.next_value_seed(self.nested(Member::Bindings))?
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(binding) = seq.next_element()? {
+            let handled = self.handler.binding(binding);
+            self.handled(handled)?;
+        }
+        Ok(())
+    }
+}
+
+/// RFC 4180 records, read a line at a time; quoted values may hold commas,
+/// line breaks and `""` for a quote.
+struct CsvRecords<R> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> CsvRecords<R> {
+    fn next_record(&mut self) -> Result<Option<Vec<String>>, ReadError> {
+        let mut record = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            self.line.clear();
+            if self
+                .reader
+                .read_line(&mut self.line)
+                .map_err(ReadError::Io)?
+                == 0
+            {
+                if quoted {
+                    return Err(ReadError::Io(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "unterminated quoted value",
+                    )));
+                }
+                if record.is_empty() && field.is_empty() {
+                    return Ok(None);
+                }
+                record.push(field);
+                return Ok(Some(record));
+            }
+            let mut chars = self.line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (quoted, c) {
+                    (true, '"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    (true, '"') => quoted = false,
+                    (true, c) => field.push(c),
+                    (false, '"') if field.is_empty() => quoted = true,
+                    (false, ',') => record.push(std::mem::take(&mut field)),
+                    (false, '\r') if chars.peek() == Some(&'\n') => {}
+                    (false, '\n') => {
+                        record.push(field);
+                        return Ok(Some(record));
+                    }
+                    (false, c) => field.push(c),
+                }
+            }
+        }
+    }
+}
+
+fn csv_object(value: String) -> Object {
+    if let Some(label) = value.strip_prefix("_:") {
+        return Object {
+            r#type: TermType::Bnode,
+            value: label.to_string(),
+            ..Object::default()
+        };
+    }
+    let is_iri = (value.starts_with("http://") || value.starts_with("https://"))
+        && !value.contains(char::is_whitespace);
+    Object {
+        r#type: if is_iri {
+            TermType::Uri
+        } else {
+            TermType::Literal
+        },
+        value,
+        ..Object::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything a response is read into, in the order it was read.
+    #[derive(Debug, Default)]
+    struct Collected {
+        head: Option<Head>,
+        /// How many bindings came before the head.
+        head_after: usize,
+        bindings: Vec<HashMap<String, Object>>,
+        boolean: Option<bool>,
+    }
+
+    impl ResultHandler for Collected {
+        fn head(&mut self, head: Head) -> io::Result<()> {
+            self.head = Some(head);
+            self.head_after = self.bindings.len();
+            Ok(())
+        }
+
+        fn binding(&mut self, binding: HashMap<String, Object>) -> io::Result<()> {
+            self.bindings.push(binding);
+            Ok(())
+        }
+
+        fn boolean(&mut self, boolean: bool) {
+            self.boolean = Some(boolean);
+        }
+    }
+
+    fn collect(format: ResultFormat, body: &str) -> (Collected, Result<(), ReadError>) {
+        let mut collected = Collected::default();
+        let read = format.read(body.as_bytes(), &mut collected);
+        (collected, read)
+    }
+
+    fn read(format: ResultFormat, body: &str) -> Result<Vec<HashMap<String, Object>>, ReadError> {
+        let (collected, read) = collect(format, body);
+        read.map(|()| collected.bindings)
+    }
+
+    #[test]
+    fn test_csv_records() {
+        let mut records = CsvRecords {
+            reader: "a,b\r\n\"x, \"\"y\"\"\",\r\n\"line\nbreak\",z".as_bytes(),
+            line: String::new(),
+        };
+        let mut all = vec![];
+        while let Some(record) = records.next_record().unwrap() {
+            all.push(record);
+        }
+        assert_eq!(
+            all,
+            vec![
+                vec!["a", "b"],
+                vec!["x, \"y\"", ""],
+                vec!["line\nbreak", "z"]
+            ]
+        );
+        assert!(read(ResultFormat::Csv, "a\r\n\"open").is_err());
+    }
+
+    #[test]
+    fn test_formats_read_the_same_terms() {
+        let json = r#"{"head": {"vars": ["city", "label", "population"]}, "results": {"bindings": [{
+            "city": {"type": "uri", "value": "http://www.wikidata.org/entity/Q220"},
+            "label": {"type": "literal", "xml:lang": "it", "value": "Roma"},
+            "population": {"type": "literal", "datatype": "http://www.w3.org/2001/XMLSchema#decimal", "value": "2783809"}
+        }]}}"#;
+        let xml = r#"<?xml version="1.0"?>
+<sparql xmlns="http://www.w3.org/2005/sparql-results#">
+  <head><variable name="city"/><variable name="label"/><variable name="population"/></head>
+  <results><result>
+    <binding name="city"><uri>http://www.wikidata.org/entity/Q220</uri></binding>
+    <binding name="label"><literal xml:lang="it">Roma</literal></binding>
+    <binding name="population"><literal datatype="http://www.w3.org/2001/XMLSchema#decimal">2783809</literal></binding>
+  </result></results>
+</sparql>"#;
+        let tsv = "?city\t?label\t?population\n<http://www.wikidata.org/entity/Q220>\t\"Roma\"@it\t\"2783809\"^^<http://www.w3.org/2001/XMLSchema#decimal>\n";
+        let expected = read(ResultFormat::Json, json).unwrap();
+        for (format, body) in [(ResultFormat::Xml, xml), (ResultFormat::Tsv, tsv)] {
+            let (collected, read) = collect(format, body);
+            assert!(read.is_ok(), "{:?}", format);
+            let vars = &collected.head.unwrap().vars;
+            assert_eq!(vars, &vec!["city", "label", "population"]);
+            assert_eq!(collected.bindings, expected, "{:?}", format);
+        }
+
+        let csv = "city,label,population\r\nhttp://www.wikidata.org/entity/Q220,Roma,2783809\r\n_:b0,,\r\n";
+        let bindings = read(ResultFormat::Csv, csv).unwrap();
+        let [city, node] = &bindings[..] else {
+            panic!("expected two bindings");
+        };
+        assert_eq!(city["city"], expected[0]["city"]);
+        assert_eq!(city["label"].value, "Roma");
+        assert_eq!(city["population"].datatype, None);
+        assert_eq!(node["city"].r#type, TermType::Bnode);
+        assert!(!node.contains_key("label"));
+        assert!(read(ResultFormat::Csv, "city\r\na,b\r\n").is_err());
+    }
+
+    #[test]
+    fn test_json_bindings_are_streamed() {
+        let json = r#"{
+            "results": {"distinct": false, "bindings": [
+                {"label": {"type": "literal", "value": "{\"quoted\" ] }"}},
+                {}
+            ], "ordered": true},
+            "head": {"vars": ["label"], "link": ["http://example.org/about"]},
+            "extra": [1, {"a": -2.5e3}]
+        }"#;
+        let (collected, read) = collect(ResultFormat::Json, json);
+        assert!(read.is_ok());
+        assert_eq!(collected.bindings[0]["label"].value, "{\"quoted\" ] }");
+        assert!(collected.bindings[1].is_empty());
+        assert_eq!(collected.head_after, 2);
+        assert_eq!(
+            collected.head.unwrap().link,
+            vec!["http://example.org/about"]
+        );
+
+        let (ask, read) = collect(ResultFormat::Json, r#"{"head": {}, "boolean": true}"#);
+        assert!(read.is_ok());
+        assert_eq!(ask.boolean, Some(true));
+
+        // A binding is handed over before the rest of the body is read.
+        let first = r#"{"head": {"vars": ["a"]}, "results": {"bindings": [{"a": {"type": "uri", "value": "x"}}, "#;
+        let dropped = io::Error::new(io::ErrorKind::ConnectionReset, "dropped");
+        let body = io::Read::chain(first.as_bytes(), FailingReader(Some(dropped)));
+        let mut collected = Collected::default();
+        let read = ResultFormat::Json.read(io::BufReader::new(body), &mut collected);
+        assert!(matches!(read, Err(ReadError::Io(_))));
+        assert_eq!(collected.bindings.len(), 1);
+
+        let cut = r#"{"head": {"vars": ["a"]}, "results": {"bindings": [{"a": {"type": "uri", "value": "x"}}, {"a""#;
+        let (collected, read) = collect(ResultFormat::Json, cut);
+        assert_eq!(collected.bindings.len(), 1);
+        let Err(ReadError::Io(e)) = read else {
+            panic!("a cut body is read as a transport error: {:?}", read);
+        };
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        let wrong = r#"{"head": {"vars": ["a"]}, "results": {"bindings": [{"a": 1}]}}"#;
+        let (_, read) = collect(ResultFormat::Json, wrong);
+        assert!(matches!(read, Err(ReadError::Invalid(_))));
+
+        let mut full = |_| Err(io::Error::new(io::ErrorKind::StorageFull, "full"));
+        let read = ResultFormat::Json.read(first.as_bytes(), &mut full);
+        assert!(matches!(read, Err(ReadError::Handler(_))));
+    }
+
+    /// Fails with its error once, like a connection dropped mid-body.
+    struct FailingReader(Option<io::Error>);
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            self.0.take().map_or(Ok(0), Err)
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::result_format::ResultFormat;
+use crate::store::LocalStore;
+use crate::wikidata_queries::sparql_types::{Object, SparqlResponse, TermType};
+use oxigraph::model::{BlankNode, Literal, NamedNode, Term};
+use oxigraph::sparql::results::{QueryResultsFormat, QueryResultsSerializer};
+use oxigraph::sparql::Variable;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+use urlencoding::decode;
+
+/// The store of `testdata/{name}.ttl`.
+pub fn testdata(name: &str) -> LocalStore {
+    let path = format!("{}/testdata/{}.ttl", env!("CARGO_MANIFEST_DIR"), name);
+    LocalStore::load(name, Path::new(&path)).unwrap()
+}
+
+/// Answers `GET {uri}?query=...` from a `LocalStore` until the test process exits.
+pub struct SparqlServer {
+    pub uri: String,
+}
+
+impl SparqlServer {
+    pub fn start(store: LocalStore) -> Self {
+        Self::start_dropping(store, 0)
+    }
+
+    /// Like `start`, but the connection of each of the first `dropped`
+    /// responses closes halfway through a body sent without a length, so
+    /// the client only sees it end early.
+    pub fn start_dropping(store: LocalStore, dropped: usize) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/sparql", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for (index, stream) in listener.incoming().flatten().enumerate() {
+                respond(stream, &store, index < dropped);
+            }
+        });
+        SparqlServer { uri }
+    }
+}
+
+fn respond(mut stream: TcpStream, store: &LocalStore, drop: bool) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Every request is a GET without a body; only `Accept` matters.
+    let mut format = ResultFormat::Json;
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("accept") {
+                let formats = [
+                    ResultFormat::Json,
+                    ResultFormat::Xml,
+                    ResultFormat::Csv,
+                    ResultFormat::Tsv,
+                ];
+                format = formats
+                    .into_iter()
+                    .find(|format| format.media_type() == value)
+                    .unwrap_or(format);
+            }
+        }
+        line.clear();
+    }
+
+    let query = request_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|target| target.split_once('?'))
+        .and_then(|(_, parameters)| {
+            parameters
+                .split('&')
+                .find_map(|parameter| parameter.strip_prefix("query="))
+        })
+        .and_then(|query| decode(query).ok());
+    let (status, body) = match query.map(|query| store.query(&query)) {
+        Some(Ok(response)) => ("200 OK", serialize(&response, format)),
+        Some(Err(e)) => ("400 Bad Request", e.to_string()),
+        None => ("400 Bad Request", "missing query".to_string()),
+    };
+    if drop {
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            format.media_type(),
+            &body[..body.floor_char_boundary(body.len() / 2)]
+        );
+        return;
+    }
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        format.media_type(),
+        body.len(),
+        body
+    );
+}
+
+/// Writes `response` the way an endpoint answering in `format` would.
+fn serialize(response: &SparqlResponse, format: ResultFormat) -> String {
+    let format = match format {
+        ResultFormat::Json => return serde_json::to_string(response).unwrap(),
+        ResultFormat::Xml => QueryResultsFormat::Xml,
+        ResultFormat::Csv => QueryResultsFormat::Csv,
+        ResultFormat::Tsv => QueryResultsFormat::Tsv,
+    };
+    let serializer = QueryResultsSerializer::from_format(format);
+    if let Some(boolean) = response.boolean {
+        let body = serializer.serialize_boolean_to_writer(vec![], boolean);
+        return String::from_utf8(body.unwrap()).unwrap();
+    }
+    let vars = response
+        .head
+        .vars
+        .iter()
+        .map(Variable::new_unchecked)
+        .collect();
+    let mut writer = serializer
+        .serialize_solutions_to_writer(vec![], vars)
+        .unwrap();
+    for binding in &response.results.bindings {
+        let solution: Vec<_> = binding
+            .iter()
+            .map(|(var, object)| (Variable::new_unchecked(var), term(object)))
+            .collect();
+        writer
+            .serialize(solution.iter().map(|(var, term)| (var, term)))
+            .unwrap();
+    }
+    String::from_utf8(writer.finish().unwrap()).unwrap()
+}
+
+fn term(object: &Object) -> Term {
+    match (object.r#type, &object.lang, &object.datatype) {
+        (TermType::Uri, _, _) => NamedNode::new_unchecked(&object.value).into(),
+        (TermType::Bnode, _, _) => BlankNode::new_unchecked(&object.value).into(),
+        (_, Some(lang), _) => {
+            Literal::new_language_tagged_literal_unchecked(&object.value, lang).into()
+        }
+        (_, None, Some(datatype)) => {
+            Literal::new_typed_literal(&object.value, NamedNode::new_unchecked(datatype)).into()
+        }
+        (_, None, None) => Literal::new_simple_literal(&object.value).into(),
+    }
+}
@@ -0,0 +1,159 @@
+use crate::error::Error;
+use crate::wikidata_queries::sparql_types::{Head, Object, Results, SparqlResponse};
+use oxigraph::io::RdfFormat;
+use oxigraph::sparql::{QueryResults, SparqlEvaluator};
+use oxigraph::store::Store;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// An in-memory RDF store loaded from a local extract, answering the same
+/// generated queries as a remote endpoint while offline.
+pub struct LocalStore {
+    name: String,
+    store: Store,
+}
+
+impl LocalStore {
+    /// Loads an N-Triples (`.nt`) or Turtle (`.ttl`) file for the endpoint `name`.
+    pub fn load(name: &str, path: &Path) -> Result<Self, Error> {
+        let format = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(RdfFormat::from_extension)
+            .ok_or_else(|| {
+                Error::Config(format!(
+                    "{}: cannot tell the RDF format from the file extension",
+                    path.display()
+                ))
+            })?;
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        LocalStore::from_reader(name, format, BufReader::new(file))
+    }
+
+    pub fn from_reader(name: &str, format: RdfFormat, reader: impl Read) -> Result<Self, Error> {
+        let store = Store::new().map_err(|e| Error::Query {
+            endpoint: name.to_string(),
+            message: e.to_string(),
+        })?;
+        store
+            .load_from_reader(format, reader)
+            .map_err(|e| Error::Decode {
+                source: name.to_string(),
+                message: e.to_string(),
+            })?;
+        Ok(LocalStore {
+            name: name.to_string(),
+            store,
+        })
+    }
+
+    /// Evaluates a SELECT or ASK query into the response a SPARQL endpoint would send.
+    pub fn query(&self, sparql: &str) -> Result<SparqlResponse, Error> {
+        let error = |message: String| Error::Query {
+            endpoint: self.name.clone(),
+            message,
+        };
+        let results = SparqlEvaluator::new()
+            .parse_query(sparql)
+            .map_err(|e| error(e.to_string()))?
+            .on_store(&self.store)
+            .execute()
+            .map_err(|e| error(e.to_string()))?;
+        let solutions = match results {
+            QueryResults::Solutions(solutions) => solutions,
+            QueryResults::Boolean(boolean) => {
+                return Ok(SparqlResponse {
+                    head: Head::default(),
+                    results: Results::default(),
+                    boolean: Some(boolean),
+                })
+            }
+            QueryResults::Graph(_) => {
+                return Err(error(
+                    "CONSTRUCT and DESCRIBE are not supported".to_string(),
+                ))
+            }
+        };
+        let vars = solutions
+            .variables()
+            .iter()
+            .map(|var| var.as_str().to_string())
+            .collect();
+        let mut bindings = vec![];
+        for solution in solutions {
+            let solution = solution.map_err(|e| error(e.to_string()))?;
+            let binding: HashMap<_, _> = solution
+                .iter()
+                .map(|(var, term)| (var.as_str().to_string(), Object::from(term)))
+                .collect();
+            bindings.push(binding);
+        }
+        Ok(SparqlResponse {
+            head: Head { vars, link: vec![] },
+            results: Results { bindings },
+            boolean: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endpoint::Dialect;
+    use crate::query_category::Categories;
+    use crate::sparql_server::testdata;
+    use crate::wikidata_queries::sparql_types::TermType;
+
+    #[test]
+    fn test_generated_queries_run_offline() {
+        let store = testdata("italy");
+        let categories = Categories::builtin(Dialect::Wikidata);
+
+        let sparql = categories.gen_query("country", "capital").unwrap();
+        let response = store.query(&sparql).unwrap();
+        assert!(response.head.vars.contains(&"endTime".to_string()));
+        let [binding] = &response.results.bindings[..] else {
+            panic!("expected one binding: {:?}", response.results.bindings);
+        };
+        assert_eq!(binding["capital"].r#type, TermType::Uri);
+        assert_eq!(
+            binding["capital"].value,
+            "http://www.wikidata.org/entity/Q220"
+        );
+        assert_eq!(
+            binding["startTime"].datatype.as_deref(),
+            Some("http://www.w3.org/2001/XMLSchema#dateTime")
+        );
+        assert!(!binding.contains_key("endTime"));
+
+        let sparql = categories.gen_query("country", "label").unwrap();
+        let response = store.query(&sparql).unwrap();
+        assert_eq!(response.results.bindings.len(), 2);
+        for binding in &response.results.bindings {
+            assert_eq!(binding["label"].datatype, None);
+            assert_eq!(
+                binding["label"].lang,
+                Some(binding["language"].value.clone())
+            );
+        }
+
+        let response = store.query("ASK { ?country ?p ?o }").unwrap();
+        assert_eq!(response.boolean, Some(true));
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let store = testdata("italy");
+        assert!(matches!(store.query("SELECT"), Err(Error::Query { .. })));
+        assert!(matches!(
+            store.query("CONSTRUCT WHERE { ?s ?p ?o }"),
+            Err(Error::Query { .. })
+        ));
+        assert!(matches!(
+            LocalStore::load("local", Path::new("extract.csv")),
+            Err(Error::Config(_))
+        ));
+    }
+}
@@ -7,3 +7,158 @@ pub mod league_member;
 pub mod sparql_types;
 pub mod state;
 pub mod war;
+
+use crate::query_builder::{p, ps, triple, var, Pattern, Prefix, Query, Term};
+use crate::query_category::{ReferenceVars, Statement, TimeVars, ValueVar};
+use oxigraph::model::vocab::geosparql;
+
+/// `?{target}` with the `?{target}Precision` and `?{target}Calendar` of its
+/// time value.
+pub fn time_var(target: &str) -> ValueVar {
+    ValueVar {
+        name: target.to_string(),
+        time: Some(time_parts(target)),
+        datatype: None,
+    }
+}
+
+fn time_parts(target: &str) -> TimeVars {
+    TimeVars {
+        precision: format!("{}Precision", target),
+        calendar: format!("{}Calendar", target),
+    }
+}
+
+/// The variables of `time_var`, in the order queries project them.
+pub fn time_vars(target: &str) -> Vec<String> {
+    time_var(target).vars()
+}
+
+/// Reads the time value node `?{target}_value` into the variables of `time_var`.
+fn time_value_node(target: &str) -> Vec<Pattern> {
+    let node = || var(&format!("{}_value", target));
+    let wikibase = |name: &str| Prefix::Wikibase.term(name);
+    let time = time_parts(target);
+    vec![
+        triple(node(), wikibase("timeValue"), var(target)),
+        triple(node(), wikibase("timePrecision"), var(&time.precision)),
+        triple(node(), wikibase("timeCalendarModel"), var(&time.calendar)),
+    ]
+}
+
+/// `?{target}Rank`, the `wikibase:rank` of the statement `?{target}` is the
+/// value of.
+pub fn rank_var(target: &str) -> String {
+    format!("{}Rank", target)
+}
+
+/// The statement of the values `statement_value` binds.
+pub fn ranked(target: &str) -> Statement {
+    Statement::new(ValueVar::plain(target)).rank(rank_var(target))
+}
+
+/// The statement of the `geo:wktLiteral` points `statement_value` binds to
+/// `?coordinates`.
+pub fn ranked_coordinates() -> Statement {
+    ranked("coordinates").typed(geosparql::WKT_LITERAL.as_str())
+}
+
+/// The statement of the time values `time_value` binds.
+pub fn ranked_time(target: &str) -> Statement {
+    Statement::new(time_var(target)).rank(rank_var(target))
+}
+
+/// Binds the rank of `statement` to `?{target}Rank`.
+pub fn statement_rank(statement: Term, target: &str) -> Pattern {
+    triple(
+        statement,
+        Prefix::Wikibase.term("rank"),
+        var(&rank_var(target)),
+    )
+}
+
+/// The values of every `property` statement of `?{subject}`, whatever its
+/// rank, with the rank in `?{target}Rank`. Unlike `wdt:`, which only returns
+/// the best-ranked values, this lets merge choose which ranks to keep.
+pub fn statement_value(subject: &str, property: &str, target: &str) -> Vec<Pattern> {
+    let statement = || var(&format!("{}_statement", target));
+    vec![
+        triple(var(subject), p(property), statement()),
+        triple(statement(), ps(property), var(target)),
+        statement_rank(statement(), target),
+    ]
+}
+
+/// The time values of every `property` statement of `?{subject}` with their
+/// rank, and with the precision and calendar model that the truthy value drops.
+pub fn time_value(subject: &str, property: &str, target: &str) -> Vec<Pattern> {
+    let statement = || var(&format!("{}_statement", target));
+    let mut patterns = vec![
+        triple(var(subject), p(property), statement()),
+        statement_rank(statement(), target),
+        triple(
+            statement(),
+            Prefix::Psv.term(property),
+            var(&format!("{}_value", target)),
+        ),
+    ];
+    patterns.extend(time_value_node(target));
+    patterns
+}
+
+/// The time value of the `property` qualifier of `statement`, if it has one.
+pub fn time_qualifier(statement: Term, property: &str, target: &str) -> Pattern {
+    let mut patterns = vec![triple(
+        statement,
+        Prefix::Pqv.term(property),
+        var(&format!("{}_value", target)),
+    )];
+    patterns.extend(time_value_node(target));
+    Pattern::Optional(patterns)
+}
+
+/// `?{target}StatedIn`, `?{target}ReferenceUrl` and `?{target}Retrieved`, the
+/// variables `with_references` binds.
+pub fn reference_vars(target: &str) -> ReferenceVars {
+    ReferenceVars {
+        stated_in: format!("{}StatedIn", target),
+        reference_url: format!("{}ReferenceUrl", target),
+        retrieved: format!("{}Retrieved", target),
+    }
+}
+
+/// Whether `query` reads `?{target}` from the statement `?{target}_statement`.
+pub fn reads_statement(query: &Query, target: &str) -> bool {
+    let statement = format!("{}_statement", target);
+    query.patterns.iter().any(|pattern| {
+        matches!(pattern, Pattern::Triple { subject: Term::Var(var), .. } if *var == statement)
+    })
+}
+
+/// `query` with the stated in (P248), reference URL (P854) and retrieved (P813)
+/// of every `prov:wasDerivedFrom` reference of the `?{target}_statement` it
+/// reads `?{target}` from. Queries that read no such statement, such as
+/// labels, are returned as they are.
+pub fn with_references(query: Query, target: &str) -> Query {
+    if !reads_statement(&query, target) {
+        return query;
+    }
+    let statement = format!("{}_statement", target);
+    let reference = || var(&format!("{}_reference", target));
+    let vars = reference_vars(target).vars();
+    let mut patterns = vec![triple(
+        var(&statement),
+        Prefix::Prov.term("wasDerivedFrom"),
+        reference(),
+    )];
+    for (property, name) in ["P248", "P854", "P813"].iter().zip(&vars) {
+        patterns.push(Pattern::Optional(vec![triple(
+            reference(),
+            Prefix::Pr.term(property),
+            var(name),
+        )]));
+    }
+    let mut query = query.pattern(Pattern::Optional(patterns));
+    query.vars.extend(vars);
+    query
+}
@@ -1,6 +1,13 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, triple, var, wd, wdt, Pattern, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
+use crate::wikidata_queries::{
+    rank_var, ranked, ranked_coordinates, ranked_time, statement_value, time_value, time_vars,
+};
 use strum_macros::{Display, EnumIter, EnumString};
 
-use crate::wikidata_queries::country::COUNTRY_QUERY;
+use crate::wikidata_queries::country::is_country;
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
 pub enum BattleQuery {
@@ -22,82 +29,78 @@ pub enum BattleQuery {
     Unknown,
 }
 
-static BATTLE_QUERY: &str = "
-    ?battle wdt:P31 wd:Q178561 .
-";
+fn is_battle() -> Pattern {
+    triple(var("battle"), wdt("P31"), wd("Q178561"))
+}
 
-pub fn gen_battle_query(battle_query: BattleQuery) -> String {
-    let result = match battle_query {
-        BattleQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?battle ?label ?language WHERE {{
-                    {}
-                    ?battle rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}",
-                BATTLE_QUERY
-            )
-        }
-        BattleQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?battle ?coordinates WHERE {{
-                    {}
-                    ?battle wdt:P625 ?coordinates .
-                }}",
-                BATTLE_QUERY
-            )
-        }
-        BattleQuery::PartOf => {
-            format!(
-                "SELECT DISTINCT ?battle ?partOf WHERE {{
-                    {}
-                    ?battle wdt:P361 ?partOf .
-                }}",
-                BATTLE_QUERY
-            )
-        }
+pub fn gen_battle_query(battle_query: BattleQuery) -> Option<Query> {
+    let property = |target: &str, property: &str| {
+        Query::select_distinct(&["battle", target, &rank_var(target)])
+            .pattern(is_battle())
+            .patterns(statement_value("battle", property, target))
+    };
+    let time = |target: &str, property: &str| {
+        let mut vars = vec!["battle".to_string()];
+        vars.extend(time_vars(target));
+        vars.push(rank_var(target));
+        Query::select_distinct(&vars)
+            .pattern(is_battle())
+            .patterns(time_value("battle", property, target))
+    };
+    let query = match battle_query {
+        BattleQuery::Label => Query::select_distinct(&["battle", "label", "language"])
+            .pattern(is_battle())
+            .patterns(labelled("battle")),
+        BattleQuery::Coordinates => property("coordinates", "P625"),
+        BattleQuery::PartOf => property("partOf", "P361"),
         BattleQuery::Person => {
-            format!(
-                "SELECT DISTINCT ?battle ?person WHERE {{
-                    {}
-                    ?battle wdt:P710 ?person .
-                    ?person wdt:P31 wd:Q5 .
-                }}",
-                BATTLE_QUERY
-            )
-        }
-        BattleQuery::Country => {
-            format!(
-                "SELECT DISTINCT ?battle ?country WHERE {{
-                    {}
-                    ?battle wdt:P710 ?country .
-                    {}
-                }}",
-                BATTLE_QUERY, COUNTRY_QUERY
-            )
-        }
-        BattleQuery::PointInTime => {
-            format!(
-                "SELECT DISTINCT ?battle ?pointInTime WHERE {{
-                    {}
-                    ?battle wdt:P585 ?pointInTime .
-                }}",
-                BATTLE_QUERY
-            )
-        }
-        BattleQuery::Image => {
-            format!(
-                "SELECT DISTINCT ?battle ?image WHERE {{
-                    {}
-                    ?battle wdt:P18 ?image .
-                }}",
-                BATTLE_QUERY
-            )
+            property("person", "P710").pattern(triple(var("person"), wdt("P31"), wd("Q5")))
         }
+        BattleQuery::Country => property("country", "P710").pattern(is_country("country")),
+        BattleQuery::PointInTime => time("pointInTime", "P585"),
+        BattleQuery::Image => property("image", "P18"),
+        BattleQuery::Unknown => return None,
+    };
+    Some(query)
+}
 
-        BattleQuery::Unknown => "Unknown".to_string(),
+/// How merge reads the bindings of each query of `gen_battle_query`.
+pub fn battle_statement(battle_query: BattleQuery) -> Option<Statement> {
+    let statement = match battle_query {
+        BattleQuery::Label => Statement::text("label", "language"),
+        BattleQuery::Coordinates => ranked_coordinates(),
+        BattleQuery::PartOf => ranked("partOf"),
+        BattleQuery::Person => ranked("person"),
+        BattleQuery::Country => ranked("country"),
+        BattleQuery::PointInTime => ranked_time("pointInTime"),
+        BattleQuery::Image => ranked("image"),
+        BattleQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct Battle;
+
+impl QueryCategory for Battle {
+    fn name(&self) -> &str {
+        "battle"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<BattleQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<BattleQuery>(target).and_then(gen_battle_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<BattleQuery>(target).and_then(battle_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Battle>()
+    }
 }
 
 #[cfg(test)]
@@ -106,17 +109,15 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_battles() {
         for variant in BattleQuery::iter() {
             if variant == BattleQuery::Unknown {
                 continue;
             }
-            let query = gen_battle_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_battle_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "battle");
+            assert_eq!(query.patterns[0], is_battle());
         }
     }
 }
@@ -1,6 +1,11 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, triple, var, wdt, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
 use strum_macros::{Display, EnumIter, EnumString};
 
-use crate::wikidata_queries::country::COUNTRY_QUERY;
+use crate::wikidata_queries::country::is_country;
+use crate::wikidata_queries::{rank_var, ranked_coordinates, statement_value};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
 pub enum CapitalQuery {
@@ -12,33 +17,55 @@ pub enum CapitalQuery {
     Unknown,
 }
 
-pub fn gen_capital_query(capital_query: CapitalQuery) -> String {
-    let result = match capital_query {
+pub fn gen_capital_query(capital_query: CapitalQuery) -> Option<Query> {
+    let base = |vars: &[&str]| {
+        Query::select_distinct(vars)
+            .pattern(is_country("country"))
+            .pattern(triple(var("country"), wdt("P36"), var("capital")))
+    };
+    let query = match capital_query {
         CapitalQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?capital ?label ?language WHERE {{
-                    {}
-                    ?country wdt:P36 ?capital .
-                    ?capital rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}
-            ",
-                COUNTRY_QUERY
-            )
-        }
-        CapitalQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?capital ?coordinates WHERE {{
-                    {}
-                    ?country wdt:P36 ?capital .
-                    ?capital wdt:P625 ?coordinates .
-                }}",
-                COUNTRY_QUERY
-            )
+            base(&["capital", "label", "language"]).patterns(labelled("capital"))
         }
-        CapitalQuery::Unknown => "Unknown".to_string(),
+        CapitalQuery::Coordinates => base(&["capital", "coordinates", &rank_var("coordinates")])
+            .patterns(statement_value("capital", "P625", "coordinates")),
+        CapitalQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_capital_query`.
+pub fn capital_statement(capital_query: CapitalQuery) -> Option<Statement> {
+    let statement = match capital_query {
+        CapitalQuery::Label => Statement::text("label", "language"),
+        CapitalQuery::Coordinates => ranked_coordinates(),
+        CapitalQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct Capital;
+
+impl QueryCategory for Capital {
+    fn name(&self) -> &str {
+        "capital"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<CapitalQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<CapitalQuery>(target).and_then(gen_capital_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<CapitalQuery>(target).and_then(capital_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Capital>()
+    }
 }
 
 #[cfg(test)]
@@ -47,17 +74,15 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_capitals() {
         for variant in CapitalQuery::iter() {
             if variant == CapitalQuery::Unknown {
                 continue;
             }
-            let query = gen_capital_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_capital_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "capital");
+            assert!(!query.vars.contains(&"country".to_string()));
         }
     }
 }
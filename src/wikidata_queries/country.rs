@@ -1,3 +1,11 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, p, ps, triple, var, wd, wdt, Pattern, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
+use crate::wikidata_queries::{
+    rank_var, ranked, ranked_coordinates, ranked_time, statement_rank, statement_value,
+    time_qualifier, time_value, time_var, time_vars,
+};
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
@@ -18,84 +26,109 @@ pub enum CountryQuery {
     Unknown,
 }
 
-pub static COUNTRY_QUERY: &str = "
-{
-?country wdt:P31 wd:Q6256 .
-} UNION {
-?country wdt:P31 wd:Q3024240 .
+/// `?{subject}` is a country (Q6256) or a historical country (Q3024240).
+pub fn is_country(subject: &str) -> Pattern {
+    Pattern::Union(vec![
+        vec![triple(var(subject), wdt("P31"), wd("Q6256"))],
+        vec![triple(var(subject), wdt("P31"), wd("Q3024240"))],
+    ])
 }
-";
-
-pub fn gen_country_query(country_query: CountryQuery) -> String {
-    let result = match country_query {
-        CountryQuery::Inception => {
-            format!(
-                "SELECT DISTINCT ?country ?inception WHERE {{
-                    {}
-                    ?country wdt:P571 ?inception .
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Dissolution => {
-            format!(
-                "SELECT DISTINCT ?country ?dissolution WHERE {{
-                    {}
-                    ?country wdt:P576 ?dissolution .
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?country ?coordinates WHERE {{
-                    {}
-                    ?country wdt:P625 ?coordinates .
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Capital => {
-            format!(
-                "SELECT DISTINCT ?country ?capital ?startTime ?endTime ?pointInTime WHERE {{
-                    {}
-                    ?country p:P36 ?capital_statement .
-                    ?capital_statement ps:P36 ?capital .
-                    OPTIONAL {{
-                        ?capital_statement pq:P580 ?startTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P582 ?endTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P585 ?pointInTime .
-                    }}
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?country ?label ?language WHERE {{
-                    {}
-                    ?country rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Flag => {
-            format!(
-                "SELECT DISTINCT ?country ?flag WHERE {{
-                    {}
-                    ?country wdt:P41 ?flag .
-                }}",
-                COUNTRY_QUERY
-            )
-        }
-        CountryQuery::Unknown => "Unknown".to_string(),
+
+/// The capitals (P36) of `?{subject}` with the rank and the start, end and
+/// point in time qualifiers of each capital statement.
+pub fn capital_statements(subject: &str) -> Vec<Pattern> {
+    let statement = || var("capital_statement");
+    vec![
+        triple(var(subject), p("P36"), statement()),
+        triple(statement(), ps("P36"), var("capital")),
+        statement_rank(statement(), "capital"),
+        time_qualifier(statement(), "P580", "startTime"),
+        time_qualifier(statement(), "P582", "endTime"),
+        time_qualifier(statement(), "P585", "pointInTime"),
+    ]
+}
+
+/// The statement `capital_statements` binds.
+pub fn qualified_capital() -> Statement {
+    ranked("capital")
+        .qualifier(time_var("startTime"))
+        .qualifier(time_var("endTime"))
+        .qualifier(time_var("pointInTime"))
+}
+
+/// Variables bound by `capital_statements`.
+pub fn capital_vars(subject: &str) -> Vec<String> {
+    let mut vars = vec![subject.to_string()];
+    vars.extend(qualified_capital().vars());
+    vars
+}
+
+pub fn gen_country_query(country_query: CountryQuery) -> Option<Query> {
+    let property = |target: &str, property: &str| {
+        Query::select_distinct(&["country", target, &rank_var(target)])
+            .pattern(is_country("country"))
+            .patterns(statement_value("country", property, target))
+    };
+    let time = |target: &str, property: &str| {
+        let mut vars = vec!["country".to_string()];
+        vars.extend(time_vars(target));
+        vars.push(rank_var(target));
+        Query::select_distinct(&vars)
+            .pattern(is_country("country"))
+            .patterns(time_value("country", property, target))
+    };
+    let query = match country_query {
+        CountryQuery::Inception => time("inception", "P571"),
+        CountryQuery::Dissolution => time("dissolution", "P576"),
+        CountryQuery::Coordinates => property("coordinates", "P625"),
+        CountryQuery::Capital => Query::select_distinct(&capital_vars("country"))
+            .pattern(is_country("country"))
+            .patterns(capital_statements("country")),
+        CountryQuery::Label => Query::select_distinct(&["country", "label", "language"])
+            .pattern(is_country("country"))
+            .patterns(labelled("country")),
+        CountryQuery::Flag => property("flag", "P41"),
+        CountryQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_country_query`.
+pub fn country_statement(country_query: CountryQuery) -> Option<Statement> {
+    let statement = match country_query {
+        CountryQuery::Inception => ranked_time("inception"),
+        CountryQuery::Dissolution => ranked_time("dissolution"),
+        CountryQuery::Coordinates => ranked_coordinates(),
+        CountryQuery::Capital => qualified_capital(),
+        CountryQuery::Label => Statement::text("label", "language"),
+        CountryQuery::Flag => ranked("flag"),
+        CountryQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct Country;
+
+impl QueryCategory for Country {
+    fn name(&self) -> &str {
+        "country"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<CountryQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<CountryQuery>(target).and_then(gen_country_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<CountryQuery>(target).and_then(country_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::Country>()
+    }
 }
 
 #[cfg(test)]
@@ -104,17 +137,45 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_countries() {
         for variant in CountryQuery::iter() {
             if variant == CountryQuery::Unknown {
+                assert_eq!(gen_country_query(variant), None);
                 continue;
             }
-            let query = gen_country_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_country_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "country");
+            assert_eq!(query.patterns[0], is_country("country"));
         }
     }
+
+    #[test]
+    fn test_capital_qualifiers_are_optional() {
+        let query = gen_country_query(CountryQuery::Capital).unwrap();
+        let optional = query
+            .patterns
+            .iter()
+            .filter(|pattern| matches!(pattern, Pattern::Optional(_)))
+            .count();
+        assert_eq!(optional, 3);
+    }
+
+    #[test]
+    fn test_dates_keep_precision_and_calendar() {
+        let query = gen_country_query(CountryQuery::Inception).unwrap();
+        assert_eq!(
+            query.vars,
+            vec![
+                "country",
+                "inception",
+                "inceptionPrecision",
+                "inceptionCalendar",
+                "inceptionRank"
+            ]
+        );
+        let sparql = query.render(crate::endpoint::Dialect::Wikidata).unwrap();
+        assert!(sparql.contains("?inception_statement wikibase:rank ?inceptionRank ."));
+        assert!(sparql.contains("?inception_value wikibase:timePrecision ?inceptionPrecision ."));
+    }
 }
@@ -1,3 +1,10 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, triple, var, wd, wdt, Path, Pattern, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement, ValueVar};
+use crate::wikidata_queries::{
+    rank_var, ranked, ranked_time, statement_value, time_value, time_vars,
+};
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
@@ -16,95 +23,95 @@ pub enum LeagueQuery {
     Unknown,
 }
 
-pub static LEAGUE_QUERY: &str = "
-    ?league wdt:P31 wd:Q170156 .
-    FILTER NOT EXISTS { ?league wdt:P31 wd:Q6256 . }
-    FILTER NOT EXISTS { ?league wdt:P31 wd:Q3024240 . }
-    {
-        ?state wdt:P463 ?league .
-    } UNION {
-        ?league wdt:P150 ?state .
+/// Leagues (Q170156) that are not countries, with every `?state` that is a
+/// member (P463) or a constituent (P150) of the league.
+pub fn league_patterns() -> Vec<Pattern> {
+    let league_is = |class: &str| triple(var("league"), wdt("P31"), wd(class));
+    let state_is = |class: &str| vec![triple(var("state"), wdt("P31"), wd(class))];
+    vec![
+        league_is("Q170156"),
+        Pattern::FilterNotExists(vec![league_is("Q6256")]),
+        Pattern::FilterNotExists(vec![league_is("Q3024240")]),
+        triple(
+            var("league"),
+            Path::from(wdt("P463")).inverse().or(wdt("P150")),
+            var("state"),
+        ),
+        Pattern::Union(vec![
+            state_is("Q6256"),
+            state_is("Q3024240"),
+            state_is("Q7275"),
+            vec![triple(
+                var("state"),
+                Path::from(wdt("P31")).then(Path::from(wdt("P279")).zero_or_more()),
+                wd("Q515"),
+            )],
+            state_is("Q133442"),
+            state_is("Q148837"),
+        ]),
+    ]
+}
+
+pub fn gen_league_query(league_query: LeagueQuery) -> Option<Query> {
+    let select = |vars: &[&str]| Query::select_distinct(vars).patterns(league_patterns());
+    let property = |target: &str, property: &str| {
+        select(&["league", target, &rank_var(target)])
+            .patterns(statement_value("league", property, target))
+    };
+    let time = |target: &str, property: &str| {
+        let mut vars = vec!["league".to_string()];
+        vars.extend(time_vars(target));
+        vars.push(rank_var(target));
+        Query::select_distinct(&vars)
+            .patterns(league_patterns())
+            .patterns(time_value("league", property, target))
+    };
+    let query = match league_query {
+        LeagueQuery::Label => select(&["league", "label", "language"]).patterns(labelled("league")),
+        LeagueQuery::Inception => time("inception", "P571"),
+        LeagueQuery::Dissolution => time("dissolution", "P576"),
+        LeagueQuery::State => select(&["league", "state"]),
+        LeagueQuery::Flag => property("flag", "P41"),
+        LeagueQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_league_query`.
+pub fn league_statement(league_query: LeagueQuery) -> Option<Statement> {
+    let statement = match league_query {
+        LeagueQuery::Label => Statement::text("label", "language"),
+        LeagueQuery::Inception => ranked_time("inception"),
+        LeagueQuery::Dissolution => ranked_time("dissolution"),
+        LeagueQuery::State => Statement::new(ValueVar::plain("state")),
+        LeagueQuery::Flag => ranked("flag"),
+        LeagueQuery::Unknown => return None,
+    };
+    Some(statement)
+}
+
+pub struct League;
+
+impl QueryCategory for League {
+    fn name(&self) -> &str {
+        "league"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<LeagueQuery>()
     }
-    {
-        ?state wdt:P31 wd:Q6256 .
-    } UNION {
-        ?state wdt:P31 wd:Q3024240 .
-    } UNION {
-        ?state wdt:P31 wd:Q7275 .
-    } UNION {
-        ?state wdt:P31/wdt:P279* wd:Q515 .
-    } UNION {
-        ?state wdt:P31 wd:Q133442 .
-    } UNION {
-        ?state wdt:P31 wd:Q148837 .
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<LeagueQuery>(target).and_then(gen_league_query)
     }
-";
 
-pub fn gen_league_query(league_query: LeagueQuery) -> String {
-    let result = match league_query {
-        LeagueQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?league ?label ?language WHERE {{
-                    {}
-                    ?league rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueQuery::Inception => {
-            format!(
-                "SELECT DISTINCT ?league ?inception WHERE {{
-                    {}
-                    ?league wdt:P571 ?inception .
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueQuery::Dissolution => {
-            format!(
-                "SELECT DISTINCT ?league ?dissolution WHERE {{
-                    {}
-                    ?league wdt:P576 ?dissolution .
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueQuery::State => {
-            format!(
-                "SELECT DISTINCT ?league ?state WHERE {{
-                    {}
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueQuery::Flag => {
-            format!(
-                "SELECT DISTINCT ?league ?flag WHERE {{
-                    {}
-                    ?league wdt:P41 ?flag .
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueQuery::Unknown => {
-            format!(
-                "SELECT DISTINCT ?league ?label ?language WHERE {{
-                    {}
-                    ?league rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-    };
-    result
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<LeagueQuery>(target).and_then(league_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::League>()
+    }
 }
 
 #[cfg(test)]
@@ -113,17 +120,15 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_leagues() {
         for variant in LeagueQuery::iter() {
             if variant == LeagueQuery::Unknown {
                 continue;
             }
-            let query = gen_league_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_league_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "league");
+            assert!(query.patterns.starts_with(&league_patterns()));
         }
     }
 }
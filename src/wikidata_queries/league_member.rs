@@ -1,4 +1,9 @@
-use crate::wikidata_queries::league::LEAGUE_QUERY;
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{bind, labelled, var, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
+use crate::wikidata_queries::league::league_patterns;
+use crate::wikidata_queries::{rank_var, ranked, ranked_coordinates, statement_value};
 use strum_macros::{Display, EnumIter, EnumString};
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
 pub enum LeagueMemberQuery {
@@ -13,45 +18,63 @@ pub enum LeagueMemberQuery {
     Unknown,
 }
 
-pub fn gen_league_member_query(league_member_query: LeagueMemberQuery) -> String {
-    let result = match league_member_query {
+pub fn gen_league_member_query(league_member_query: LeagueMemberQuery) -> Option<Query> {
+    let select = |vars: &[&str]| {
+        Query::select_distinct(vars)
+            .patterns(league_patterns())
+            .pattern(bind(var("state"), "league_member"))
+    };
+    let property = |target: &str, property: &str| {
+        select(&["league_member", target, &rank_var(target)]).patterns(statement_value(
+            "league_member",
+            property,
+            target,
+        ))
+    };
+    let query = match league_member_query {
         LeagueMemberQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?league_member ?label ?language WHERE {{
-                    {}
-                    BIND (?state AS ?league_member)
-                    ?league_member rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueMemberQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?league_member ?coordinates WHERE {{
-                    {}
-                    BIND (?state AS ?league_member)
-                    ?league_member wdt:P625 ?coordinates .
-                }}
-                ",
-                LEAGUE_QUERY
-            )
+            select(&["league_member", "label", "language"]).patterns(labelled("league_member"))
         }
-        LeagueMemberQuery::Flag => {
-            format!(
-                "SELECT DISTINCT ?league_member ?flag WHERE {{
-                    {}
-                    BIND (?state AS ?league_member)
-                    ?league_member wdt:P41 ?flag .
-                }}
-                ",
-                LEAGUE_QUERY
-            )
-        }
-        LeagueMemberQuery::Unknown => "".to_string(),
+        LeagueMemberQuery::Coordinates => property("coordinates", "P625"),
+        LeagueMemberQuery::Flag => property("flag", "P41"),
+        LeagueMemberQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_league_member_query`.
+pub fn league_member_statement(league_member_query: LeagueMemberQuery) -> Option<Statement> {
+    let statement = match league_member_query {
+        LeagueMemberQuery::Label => Statement::text("label", "language"),
+        LeagueMemberQuery::Coordinates => ranked_coordinates(),
+        LeagueMemberQuery::Flag => ranked("flag"),
+        LeagueMemberQuery::Unknown => return None,
     };
-    result
+    Some(statement)
+}
+
+pub struct LeagueMember;
+
+impl QueryCategory for LeagueMember {
+    fn name(&self) -> &str {
+        "league_member"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<LeagueMemberQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<LeagueMemberQuery>(target).and_then(gen_league_member_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<LeagueMemberQuery>(target).and_then(league_member_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::LeagueMember>()
+    }
 }
 
 #[cfg(test)]
@@ -60,17 +83,14 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_league_members() {
         for variant in LeagueMemberQuery::iter() {
             if variant == LeagueMemberQuery::Unknown {
                 continue;
             }
-            let query = gen_league_member_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_league_member_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "league_member");
         }
     }
 }
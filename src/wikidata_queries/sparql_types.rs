@@ -1,25 +1,223 @@
+use crate::date::{Calendar, HistoricalDate, Precision};
+use crate::geo::{is_wkt_datatype, parse_wkt_point, Coordinates, WktError};
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::Term;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Numeric XSD datatypes: decimal, the integer types derived from it, and floats.
+const NUMBERS: [&str; 16] = [
+    "decimal",
+    "integer",
+    "nonPositiveInteger",
+    "negativeInteger",
+    "long",
+    "int",
+    "short",
+    "byte",
+    "nonNegativeInteger",
+    "unsignedLong",
+    "unsignedInt",
+    "unsignedShort",
+    "unsignedByte",
+    "positiveInteger",
+    "double",
+    "float",
+];
+
+/// A response in the SPARQL 1.1 Query Results JSON Format: the bindings of a
+/// SELECT query, or the `boolean` of an ASK query with empty `results`.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SparqlResponse {
     pub head: Head,
+    #[serde(default)]
     pub results: Results,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boolean: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Head {
+    /// Projected variables; ASK responses have none.
+    #[serde(default)]
     pub vars: Vec<String>,
+    /// IRIs of metadata about the results.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub link: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Results {
     pub bindings: Vec<HashMap<String, Object>>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum TermType {
+    Uri,
+    #[default]
+    Literal,
+    /// A literal with a datatype, as Virtuoso writes them.
+    TypedLiteral,
+    Bnode,
+}
+
+/// One bound RDF term.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
 pub struct Object {
-    pub r#type: String,
+    pub r#type: TermType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub datatype: Option<String>,
+    /// The language tag of a literal.
+    #[serde(rename = "xml:lang", default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
     pub value: String,
 }
+
+impl Object {
+    /// The XSD datatype of a typed literal without its namespace, e.g. `dateTime`.
+    fn xsd_datatype(&self) -> Option<&str> {
+        self.datatype.as_deref()?.strip_prefix(XSD)
+    }
+
+    /// An `xsd:date`, `xsd:dateTime`, `xsd:gYearMonth` or `xsd:gYear` literal
+    /// as a Gregorian date of the precision of its datatype.
+    pub fn as_date(&self) -> Option<HistoricalDate> {
+        let precision = match self.xsd_datatype()? {
+            "gYear" => Precision::Year,
+            "gYearMonth" => Precision::Month,
+            "date" | "dateTime" => Precision::Day,
+            _ => return None,
+        };
+        HistoricalDate::parse(&self.value, precision, Calendar::Gregorian)
+    }
+
+    /// An `xsd:decimal`, any integer type derived from it, `xsd:double` or
+    /// `xsd:float` literal. Wikidata writes quantities as decimals with a sign,
+    /// e.g. `+2783809`.
+    pub fn as_decimal(&self) -> Option<f64> {
+        if !NUMBERS.contains(&self.xsd_datatype()?) {
+            return None;
+        }
+        self.value.parse().ok()
+    }
+
+    /// A `geo:wktLiteral` (or Virtuoso `virtrdf:Geometry`) point; `None` for
+    /// literals of any other datatype.
+    pub fn as_wkt_point(&self) -> Option<Result<Coordinates, WktError>> {
+        let is_wkt = self.datatype.as_deref().is_some_and(is_wkt_datatype);
+        is_wkt.then(|| parse_wkt_point(&self.value))
+    }
+}
+
+/// A term as the SPARQL JSON results format writes it, leaving out the
+/// datatype of plain and language-tagged strings like public endpoints do.
+impl From<&Term> for Object {
+    fn from(term: &Term) -> Self {
+        match term {
+            Term::NamedNode(node) => Object {
+                r#type: TermType::Uri,
+                value: node.as_str().to_string(),
+                ..Object::default()
+            },
+            Term::BlankNode(node) => Object {
+                r#type: TermType::Bnode,
+                value: node.as_str().to_string(),
+                ..Object::default()
+            },
+            Term::Literal(literal) => {
+                let datatype = literal.datatype();
+                let is_string = literal.language().is_some() || datatype == xsd::STRING;
+                Object {
+                    r#type: TermType::Literal,
+                    datatype: (!is_string).then(|| datatype.as_str().to_string()),
+                    lang: literal.language().map(str::to_string),
+                    value: literal.value().to_string(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_and_ask_responses() {
+        let select: SparqlResponse = serde_json::from_str(
+            r#"{
+                "head": {"vars": ["label", "place", "node"], "link": ["http://example.org/about"]},
+                "results": {"bindings": [{
+                    "label": {"type": "literal", "xml:lang": "it", "value": "Roma"},
+                    "place": {"type": "uri", "value": "http://www.wikidata.org/entity/Q220"},
+                    "node": {"type": "bnode", "value": "b0"}
+                }]}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(select.head.link, vec!["http://example.org/about"]);
+        assert_eq!(select.boolean, None);
+        let binding = &select.results.bindings[0];
+        assert_eq!(binding["label"].lang.as_deref(), Some("it"));
+        assert_eq!(binding["place"].r#type, TermType::Uri);
+        assert_eq!(binding["node"].r#type, TermType::Bnode);
+        let json = serde_json::to_value(&binding["label"]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "literal", "xml:lang": "it", "value": "Roma"})
+        );
+
+        let ask: SparqlResponse = serde_json::from_str(r#"{"head": {}, "boolean": true}"#).unwrap();
+        assert_eq!(ask.boolean, Some(true));
+        assert!(ask.head.vars.is_empty());
+        assert!(ask.results.bindings.is_empty());
+    }
+
+    #[test]
+    fn test_typed_literals() {
+        let typed = |datatype: &str, value: &str| Object {
+            r#type: TermType::TypedLiteral,
+            datatype: Some(datatype.to_string()),
+            value: value.to_string(),
+            ..Object::default()
+        };
+        let xsd = |name: &str| format!("{}{}", XSD, name);
+
+        let date = typed(&xsd("dateTime"), "1871-07-01T00:00:00Z").as_date();
+        assert_eq!(
+            date.map(|date| date.value()),
+            Some("1871-07-01".to_string())
+        );
+        let year = typed(&xsd("gYear"), "1861").as_date().unwrap();
+        assert_eq!(year.precision, Precision::Year);
+        assert_eq!(typed(&xsd("string"), "1861").as_date(), None);
+
+        assert_eq!(
+            typed(&xsd("decimal"), "+2783809").as_decimal(),
+            Some(2783809.0)
+        );
+        assert_eq!(
+            typed(&xsd("nonNegativeInteger"), "12").as_decimal(),
+            Some(12.0)
+        );
+        assert_eq!(typed(&xsd("unsignedShort"), "7").as_decimal(), Some(7.0));
+        assert_eq!(typed(&xsd("double"), "1.5E3").as_decimal(), Some(1500.0));
+        assert_eq!(typed(&xsd("dateTime"), "1861").as_decimal(), None);
+
+        let wkt = "http://www.opengis.net/ont/geosparql#wktLiteral";
+        assert_eq!(
+            typed(wkt, "Point(12.5 41.9)").as_wkt_point(),
+            Some(Ok(Coordinates {
+                lat: 41.9,
+                lon: 12.5
+            }))
+        );
+        assert_eq!(
+            typed(&xsd("string"), "Point(12.5 41.9)").as_wkt_point(),
+            None
+        );
+    }
+}
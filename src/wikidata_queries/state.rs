@@ -1,3 +1,11 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, triple, var, wd, wdt, Pattern, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
+use crate::wikidata_queries::country::{capital_statements, capital_vars, qualified_capital};
+use crate::wikidata_queries::{
+    rank_var, ranked, ranked_coordinates, ranked_time, statement_value, time_value, time_vars,
+};
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
@@ -18,113 +26,112 @@ pub enum StateQuery {
     Unknown,
 }
 
-static STATE_QUERY: &str = "
-    {
-        ?state wdt:P31 wd:Q7275 .
-    } UNION {
-        ?state wdt:P31 wd:Q133442 .
-    } UNION {
-        ?state wdt:P31 wd:Q148837 .
-    }
-    FILTER NOT EXISTS { ?state wdt:P31 wd:Q6256 . }
-    FILTER NOT EXISTS { ?state wdt:P31 wd:Q3024240 .}
-    ?state wdt:P571 ?inception .
-    ?state wdt:P576 ?dissolution .  
-";
+/// States (Q7275), city-states (Q133442) and principalities (Q148837) that are
+/// not countries and have both an inception and a dissolution.
+fn state_patterns() -> Vec<Pattern> {
+    let is = |class: &str| triple(var("state"), wdt("P31"), wd(class));
+    vec![
+        Pattern::Values {
+            var: "state_class".to_string(),
+            values: vec![wd("Q7275"), wd("Q133442"), wd("Q148837")],
+        },
+        triple(var("state"), wdt("P31"), var("state_class")),
+        Pattern::FilterNotExists(vec![is("Q6256")]),
+        Pattern::FilterNotExists(vec![is("Q3024240")]),
+        triple(var("state"), wdt("P571"), var("anyInception")),
+        triple(var("state"), wdt("P576"), var("anyDissolution")),
+    ]
+}
 
-pub fn gen_state_query(state_query: StateQuery) -> String {
-    let result = match state_query {
-        StateQuery::Inception => {
-            format!(
-                "SELECT DISTINCT ?state ?inception WHERE {{
-                    {}
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Dissolution => {
-            format!(
-                "SELECT DISTINCT ?state ?dissolution WHERE {{
-                    {}
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?state ?coordinates WHERE {{
-                    {}
-                    ?state wdt:P625 ?coordinates .
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?state ?label ?language WHERE {{
-                    {}
-                    ?state rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language) .
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Flag => {
-            format!(
-                "SELECT DISTINCT ?state ?flag WHERE {{
-                    {}
-                    ?state wdt:P41 ?flag .
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Capital => {
-            format!(
-                "SELECT DISTINCT ?state ?capital ?startTime ?endTime ?pointInTime WHERE {{
-                    {}
-                    ?state p:P36 ?capital_statement .
-                    ?capital_statement ps:P36 ?capital .
-                    OPTIONAL {{
-                        ?capital_statement pq:P580 ?startTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P582 ?endTime .
-                    }}
-                    OPTIONAL {{
-                        ?capital_statement pq:P585 ?pointInTime .
-                    }}
-                }}
-                ",
-                STATE_QUERY
-            )
-        }
-        StateQuery::Unknown => "Unknown".to_string(),
+pub fn gen_state_query(state_query: StateQuery) -> Option<Query> {
+    let select = |vars: &[&str]| Query::select_distinct(vars).patterns(state_patterns());
+    let property = |target: &str, property: &str| {
+        select(&["state", target, &rank_var(target)])
+            .patterns(statement_value("state", property, target))
+    };
+    let time = |target: &str, property: &str| {
+        let mut vars = vec!["state".to_string()];
+        vars.extend(time_vars(target));
+        vars.push(rank_var(target));
+        Query::select_distinct(&vars)
+            .patterns(state_patterns())
+            .patterns(time_value("state", property, target))
     };
-    return result;
+    let query = match state_query {
+        StateQuery::Inception => time("inception", "P571"),
+        StateQuery::Dissolution => time("dissolution", "P576"),
+        StateQuery::Coordinates => property("coordinates", "P625"),
+        StateQuery::Label => select(&["state", "label", "language"]).patterns(labelled("state")),
+        StateQuery::Flag => property("flag", "P41"),
+        StateQuery::Capital => Query::select_distinct(&capital_vars("state"))
+            .patterns(state_patterns())
+            .patterns(capital_statements("state")),
+        StateQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_state_query`.
+pub fn state_statement(state_query: StateQuery) -> Option<Statement> {
+    let statement = match state_query {
+        StateQuery::Inception => ranked_time("inception"),
+        StateQuery::Dissolution => ranked_time("dissolution"),
+        StateQuery::Coordinates => ranked_coordinates(),
+        StateQuery::Label => Statement::text("label", "language"),
+        StateQuery::Flag => ranked("flag"),
+        StateQuery::Capital => qualified_capital(),
+        StateQuery::Unknown => return None,
+    };
+    Some(statement)
+}
+
+pub struct State;
+
+impl QueryCategory for State {
+    fn name(&self) -> &str {
+        "state"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<StateQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<StateQuery>(target).and_then(gen_state_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<StateQuery>(target).and_then(state_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::State>()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::endpoint::Dialect;
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_states() {
         for variant in StateQuery::iter() {
             if variant == StateQuery::Unknown {
                 continue;
             }
-            let query = gen_state_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_state_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "state");
+            assert!(query.patterns.starts_with(&state_patterns()));
         }
     }
+
+    #[test]
+    fn test_label_bind_has_no_trailing_dot() {
+        let query = gen_state_query(StateQuery::Label).unwrap();
+        let sparql = query.render(Dialect::Wikidata).unwrap();
+        assert!(sparql.contains("BIND (LANG(?label) AS ?language)\n"));
+    }
 }
@@ -1,6 +1,13 @@
+use crate::merge::{merger, Merger};
+use crate::model;
+use crate::query_builder::{labelled, triple, var, wd, wdt, Path, Pattern, Query};
+use crate::query_category::{enum_targets, parse_target, QueryCategory, Statement};
+use crate::wikidata_queries::{
+    rank_var, ranked, ranked_coordinates, ranked_time, statement_value, time_value, time_vars,
+};
 use strum_macros::{Display, EnumIter, EnumString};
 
-use crate::wikidata_queries::country::COUNTRY_QUERY;
+use crate::wikidata_queries::country::is_country;
 
 #[derive(Debug, PartialEq, EnumString, Display, EnumIter, Clone, Copy, Default)]
 pub enum WarQuery {
@@ -21,82 +28,84 @@ pub enum WarQuery {
     #[default]
     Unknown,
 }
-static WAR_QUERY: &str = "
-    ?war wdt:P31/wdt:P279* wd:Q198 .
-";
 
-pub fn gen_war_query(war_query: WarQuery) -> String {
-    let result = match war_query {
-        WarQuery::Label => {
-            format!(
-                "SELECT DISTINCT ?war ?label ?language WHERE {{
-                    {}
-                    ?war rdfs:label ?label .
-                    BIND (LANG(?label) AS ?language)
-                }}
-                ",
-                WAR_QUERY
-            )
-        }
-        WarQuery::Coordinates => {
-            format!(
-                "SELECT DISTINCT ?war ?coordinates WHERE {{
-                    {}
-                    ?war wdt:P625 ?coordinates .
-                }}",
-                WAR_QUERY
-            )
-        }
+/// `?war` is an instance of war (Q198) or of any subclass of it.
+fn is_war() -> Pattern {
+    triple(
+        var("war"),
+        Path::from(wdt("P31")).then(Path::from(wdt("P279")).zero_or_more()),
+        wd("Q198"),
+    )
+}
+
+pub fn gen_war_query(war_query: WarQuery) -> Option<Query> {
+    let property = |target: &str, property: &str| {
+        Query::select_distinct(&["war", target, &rank_var(target)])
+            .pattern(is_war())
+            .patterns(statement_value("war", property, target))
+    };
+    let time = |target: &str, property: &str| {
+        let mut vars = vec!["war".to_string()];
+        vars.extend(time_vars(target));
+        vars.push(rank_var(target));
+        Query::select_distinct(&vars)
+            .pattern(is_war())
+            .patterns(time_value("war", property, target))
+    };
+    let query = match war_query {
+        WarQuery::Label => Query::select_distinct(&["war", "label", "language"])
+            .pattern(is_war())
+            .patterns(labelled("war")),
+        WarQuery::Coordinates => property("coordinates", "P625"),
         WarQuery::Person => {
-            format!(
-                "SELECT DISTINCT ?war ?person WHERE {{
-                    {}
-                    ?war wdt:P710 ?person .
-                    ?person wdt:P31 wd:Q5 .
-                }}",
-                WAR_QUERY
-            )
-        }
-        WarQuery::StartDate => {
-            format!(
-                "SELECT DISTINCT ?war ?startDate WHERE {{
-                    {}
-                    ?war wdt:P580 ?startDate .
-                }}",
-                WAR_QUERY
-            )
+            property("person", "P710").pattern(triple(var("person"), wdt("P31"), wd("Q5")))
         }
-        WarQuery::EndDate => {
-            format!(
-                "SELECT DISTINCT ?war ?endDate WHERE {{
-                    {}
-                    ?war wdt:P582 ?endDate .
-                }}",
-                WAR_QUERY
-            )
-        }
-        WarQuery::Country => {
-            format!(
-                "SELECT DISTINCT ?war ?country WHERE {{
-                    {}
-                    ?war wdt:P710 ?country .
-                    {}
-                }}",
-                WAR_QUERY, COUNTRY_QUERY
-            )
-        }
-        WarQuery::Image => {
-            format!(
-                "SELECT DISTINCT ?war ?image WHERE {{
-                    {}
-                    ?war wdt:P18 ?image .
-                }}",
-                WAR_QUERY
-            )
-        }
-        WarQuery::Unknown => "Unknown".to_string(),
+        WarQuery::StartDate => time("startDate", "P580"),
+        WarQuery::EndDate => time("endDate", "P582"),
+        WarQuery::Country => property("country", "P710").pattern(is_country("country")),
+        WarQuery::Image => property("image", "P18"),
+        WarQuery::Unknown => return None,
+    };
+    Some(query)
+}
+
+/// How merge reads the bindings of each query of `gen_war_query`.
+pub fn war_statement(war_query: WarQuery) -> Option<Statement> {
+    let statement = match war_query {
+        WarQuery::Label => Statement::text("label", "language"),
+        WarQuery::Coordinates => ranked_coordinates(),
+        WarQuery::Person => ranked("person"),
+        WarQuery::StartDate => ranked_time("startDate"),
+        WarQuery::EndDate => ranked_time("endDate"),
+        WarQuery::Country => ranked("country"),
+        WarQuery::Image => ranked("image"),
+        WarQuery::Unknown => return None,
     };
-    return result;
+    Some(statement)
+}
+
+pub struct War;
+
+impl QueryCategory for War {
+    fn name(&self) -> &str {
+        "war"
+    }
+
+    fn targets(&self) -> Vec<String> {
+        enum_targets::<WarQuery>()
+    }
+
+    fn gen_query(&self, target: &str) -> Option<Query> {
+        parse_target::<WarQuery>(target).and_then(gen_war_query)
+    }
+
+    fn statement(&self, target: &str) -> Option<Statement> {
+        parse_target::<WarQuery>(target).and_then(war_statement)
+    }
+
+    fn merger(&self) -> Merger {
+        merger::<model::War>()
+    }
 }
 
 #[cfg(test)]
@@ -105,17 +114,15 @@ mod tests {
     use strum::IntoEnumIterator;
 
     #[test]
-    fn test_queries_start_with_select_distinct() {
+    fn test_queries_select_distinct_wars() {
         for variant in WarQuery::iter() {
             if variant == WarQuery::Unknown {
                 continue;
             }
-            let query = gen_war_query(variant);
-            assert!(
-                query.starts_with("SELECT DISTINCT"),
-                "Failed for variant: {:?}",
-                variant
-            );
+            let query = gen_war_query(variant).unwrap();
+            assert!(query.distinct, "Failed for variant: {:?}", variant);
+            assert_eq!(query.vars[0], "war");
+            assert_eq!(query.patterns[0], is_war());
         }
     }
 }